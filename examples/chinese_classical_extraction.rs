@@ -115,7 +115,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             for extraction in extractions {
                 categories
                     .entry(extraction.extraction_class.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(extraction);
             }
 
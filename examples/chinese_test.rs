@@ -81,7 +81,7 @@ emotions:
 
                 categories
                     .entry(category.to_string())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(&extraction.extraction_text);
             }
 
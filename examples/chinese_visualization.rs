@@ -195,7 +195,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let category = classify_extraction_category(&extraction.extraction_class);
             categories
                 .entry(category)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(&extraction.extraction_text);
         }
 
@@ -237,7 +237,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let category = classify_extraction_category(&extraction.extraction_class);
             categories
                 .entry(category)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(&extraction.extraction_text);
         }
 
@@ -254,34 +254,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("✅ 结果已保存到: {}", results_filename);
 
     // 步骤 9.5: 添加文本对齐信息
-    if let Some(extractions) = &annotated_doc.extractions {
-        if !extractions.is_empty() {
-            println!("🔧 正在为实体添加位置信息...");
-            let mut enhanced_extractions = Vec::new();
-
-            for extraction in extractions {
-                let mut enhanced = extraction.clone();
-
-                // 在文本中查找实体位置 (使用字符索引而不是字节索引)
-                if let Some(byte_start_pos) = chinese_text.find(&extraction.extraction_text) {
-                    let byte_end_pos = byte_start_pos + extraction.extraction_text.len();
-
-                    // 转换字节索引为字符索引
-                    let char_start_pos = chinese_text[..byte_start_pos].chars().count();
-                    let char_end_pos = chinese_text[..byte_end_pos].chars().count();
-
-                    enhanced.char_interval = Some(langextract::data::CharInterval {
-                        start_pos: Some(char_start_pos),
-                        end_pos: Some(char_end_pos),
-                    });
-                    enhanced.alignment_status = Some(langextract::data::AlignmentStatus::MatchExact);
-                }
-                enhanced_extractions.push(enhanced);
-            }
+    if let Some(extractions) = &annotated_doc.extractions
+        && !extractions.is_empty()
+    {
+        println!("🔧 正在为实体添加位置信息...");
+        let mut enhanced_extractions = Vec::new();
+
+        for extraction in extractions {
+            let mut enhanced = extraction.clone();
 
-            annotated_doc.extractions = Some(enhanced_extractions);
-            println!("✅ 位置信息添加完成");
+            // 在文本中查找实体位置 (使用字符索引而不是字节索引)
+            if let Some(byte_start_pos) = chinese_text.find(&extraction.extraction_text) {
+                let byte_end_pos = byte_start_pos + extraction.extraction_text.len();
+
+                // 转换字节索引为字符索引
+                let char_start_pos = chinese_text[..byte_start_pos].chars().count();
+                let char_end_pos = chinese_text[..byte_end_pos].chars().count();
+
+                enhanced.char_interval = Some(langextract::data::CharInterval {
+                    start_pos: Some(char_start_pos),
+                    end_pos: Some(char_end_pos),
+                });
+                enhanced.alignment_status = Some(langextract::data::AlignmentStatus::MatchExact);
+            }
+            enhanced_extractions.push(enhanced);
         }
+
+        annotated_doc.extractions = Some(enhanced_extractions);
+        println!("✅ 位置信息添加完成");
     }
 
     // 步骤 10: 生成 HTML 可视化
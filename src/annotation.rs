@@ -6,19 +6,44 @@ for the language model, and resolving the language model's output into
 structured annotations.
 
 Usage example:
-    let annotator = Annotator::new(language_model, prompt_template);
-    let annotated_documents = annotator.annotate_documents(documents, resolver);
+    let annotator = Annotator::new(language_model, prompt_template, format_type, None, true);
+    let annotated_documents = annotator
+        .annotate_documents(documents, &resolver, 1000, 10, false, 1, None)
+        .await?;
+
+For corpora that do not fit in memory, `annotate_stream` accepts a `Stream`
+of documents and yields annotated documents as they complete.
 */
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
-use crate::chunking::{ChunkIterator, TextChunk, make_batches_of_textchunk};
-use crate::data::{AnnotatedDocument, Document, Extraction, FormatType};
-use crate::inference::{BaseLanguageModel, InferenceOutputError};
-use crate::progress;
-use crate::prompting::{PromptTemplateStructured, QAPromptGenerator};
-use crate::resolver::AbstractResolver;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use tokio::sync::Semaphore;
+
+use crate::checkpoint::{CheckpointStore, ChunkKey};
+use crate::chunking::{
+    ChunkIterator, ChunkOverlap, SentenceIterator, TextChunk, TokenUtilError, get_char_interval, tokens_text,
+};
+use crate::data::{
+    AnnotatedDocument, AttributeValue, CharInterval, ChunkDiagnostics, ChunkErrorKind, ChunkFailure, Document,
+    Extraction, FormatType, RepairAttempt,
+};
+use crate::dry_run::{DryRunPrompt, DryRunReport, estimate_tokens};
+use crate::ensemble::Agreement;
+use crate::inference::{BaseLanguageModel, InferenceOutputError, ScoredOutput, split_reasoning};
+use crate::lenient::ParseRepair;
+use crate::merging::{FirstWins, MergeStrategy, vote_overlapping};
+use crate::prefilter::RelevanceFilter;
+use crate::progress::{self, AnnotationObserver};
+use crate::prompting::{Extraction as PromptExtraction, PromptTemplateStructured, QAPromptGenerator};
+use crate::resolver::{AbstractResolver, FUZZY_ALIGNMENT_MIN_THRESHOLD};
+use crate::tokenizer::{TokenInterval, TokenizedText, tokenize};
+use crate::verification::ExtractionVerifier;
 
 const ATTRIBUTE_SUFFIX: &str = "_attributes";
 
@@ -33,6 +58,67 @@ impl std::fmt::Display for DocumentRepeatError {
 }
 impl std::error::Error for DocumentRepeatError {}
 
+/// What to do when a chunk cannot be annotated because inference failed, the
+/// model returned no output, or the output could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Abort the run with an error on the first failed chunk.
+    #[default]
+    FailFast,
+    /// Record the failure on the document and continue with the next chunk.
+    SkipChunk,
+    /// Re-send the chunk up to `max_retries` times, then record the failure
+    /// and continue.
+    RetryChunk { max_retries: usize },
+}
+
+/// How extraction passes after the first one are prompted.
+///
+/// By default every pass sends the same prompt. Enabling `find_missed` lists
+/// the extractions earlier passes found in a chunk and asks the model only for
+/// additional ones; passes then run one after another. Varying the temperature
+/// or the example order makes later passes less likely to repeat the first.
+#[derive(Debug, Clone, Default)]
+pub struct PassPrompting {
+    /// Tell each pass what earlier passes extracted and ask only for more.
+    pub find_missed: bool,
+    /// Temperature for each pass by pass index; passes beyond the list use the
+    /// model's configured temperature.
+    pub pass_temperatures: Vec<f64>,
+    /// Rotate the few-shot examples by one position per pass.
+    pub rotate_examples: bool,
+}
+
+/// Samples every chunk several times and keeps the extractions enough samples
+/// agree on.
+///
+/// Two extractions agree when they have the same class and overlapping spans,
+/// or the same class and text when unaligned. Each kept extraction's
+/// confidence is the fraction of samples that contain it, so raising
+/// `min_votes` trades recall for precision.
+#[derive(Debug, Clone)]
+pub struct SelfConsistency {
+    /// Samples per chunk (k).
+    pub samples: usize,
+    /// Samples an extraction must appear in to be kept (m).
+    pub min_votes: usize,
+    /// Sampling temperature; `None` keeps the pass or model temperature.
+    pub temperature: Option<f64>,
+    /// Rotate the few-shot examples by one position per sample.
+    pub shuffle_examples: bool,
+}
+
+impl SelfConsistency {
+    pub fn new(samples: usize, min_votes: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            min_votes,
+            temperature: None,
+            shuffle_examples: false,
+        }
+    }
+}
+
 /// Merges extractions from multiple extraction passes.
 /// When extractions from different passes overlap in their character positions,
 /// the extraction from the earlier pass is kept (first-pass wins strategy).
@@ -48,13 +134,13 @@ pub fn merge_non_overlapping_extractions(all_extractions: &[Vec<Extraction>]) ->
     for pass_extractions in &all_extractions[1..] {
         for extraction in pass_extractions {
             let mut overlaps = false;
-            if let Some(ref _interval) = extraction.char_interval {
+            if extraction.char_interval.is_some() {
                 for existing_extraction in &merged_extractions {
-                    if let Some(ref _existing_interval) = existing_extraction.char_interval {
-                        if extractions_overlap(extraction, existing_extraction) {
-                            overlaps = true;
-                            break;
-                        }
+                    if existing_extraction.char_interval.is_some()
+                        && extractions_overlap(extraction, existing_extraction)
+                    {
+                        overlaps = true;
+                        break;
                     }
                 }
             }
@@ -107,10 +193,268 @@ pub fn document_chunk_iterator(
     Ok(chunks)
 }
 
+/// Settings shared by every document of one annotation run.
+struct AnnotationRun<'r> {
+    resolver: &'r dyn AbstractResolver,
+    max_char_buffer: usize,
+    batch_length: usize,
+    debug: bool,
+    extraction_passes: usize,
+    batch_counter: AtomicUsize,
+}
+
+impl<'r> AnnotationRun<'r> {
+    fn new(
+        resolver: &'r dyn AbstractResolver,
+        max_char_buffer: usize,
+        batch_length: usize,
+        debug: bool,
+        extraction_passes: usize,
+    ) -> Self {
+        Self {
+            resolver,
+            max_char_buffer,
+            batch_length: batch_length.max(1),
+            debug,
+            extraction_passes,
+            batch_counter: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// The result of annotating one chunk in one pass.
+struct ChunkOutcome {
+    result: Result<Vec<Extraction>, ChunkFailure>,
+    diagnostics: Option<ChunkDiagnostics>,
+}
+
+/// What is needed to prompt the parts of a split chunk like the chunk itself.
+struct SplitRequest<'a> {
+    prior: &'a [Extraction],
+    additional_context: Option<&'a str>,
+    pass_index: usize,
+    inference_kwargs: &'a Option<HashMap<String, serde_json::Value>>,
+}
+
+/// A chunk of a document with its text and position resolved once up front.
+struct DocumentChunk {
+    token_interval: TokenInterval,
+    char_interval: CharInterval,
+    text: String,
+    /// Where the text repeated from the previous chunk ends, if any.
+    overlap_end_pos: Option<usize>,
+    /// Source characters the chunk advances progress by: from the end of the
+    /// previous chunk, or the start of the document, to its own end, or to
+    /// the end of the document for the last chunk.
+    advance: usize,
+}
+
+impl DocumentChunk {
+    fn new(tokenized_text: &TokenizedText, chunk: TextChunk) -> Result<Self, TokenUtilError> {
+        let text = tokens_text(tokenized_text, &chunk.token_interval)?;
+        let char_interval = get_char_interval(tokenized_text, &chunk.token_interval)?;
+        let overlap_end_pos = chunk
+            .overlap
+            .and_then(|overlap| tokenized_text.tokens.get(overlap.end_index))
+            .map(|token| token.char_interval.start_pos);
+        Ok(Self {
+            token_interval: chunk.token_interval,
+            char_interval,
+            text,
+            overlap_end_pos,
+            advance: 0,
+        })
+    }
+
+    /// Splits the chunk in two, at the sentence boundary closest to its
+    /// middle or, for a single sentence, at its middle token. Returns nothing
+    /// when the chunk is a single token.
+    fn split(&self) -> Vec<DocumentChunk> {
+        let tokenized_text = tokenize(&self.text);
+        let num_tokens = tokenized_text.tokens.len();
+        let half_chars = self.text.chars().count() / 2;
+        let distance_from_middle = |token_index: usize| {
+            tokenized_text.tokens[token_index]
+                .char_interval
+                .start_pos
+                .abs_diff(half_chars)
+        };
+        let split_index = SentenceIterator::new(&tokenized_text, 0)
+            .map(|sentences| sentences.map(|sentence| sentence.end_index).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|end_index| *end_index > 0 && *end_index < num_tokens)
+            .min_by_key(|end_index| distance_from_middle(*end_index))
+            .unwrap_or(num_tokens / 2);
+        if split_index == 0 || split_index >= num_tokens {
+            return Vec::new();
+        }
+
+        let char_offset = self.char_interval.start_pos.unwrap_or(0);
+        [(0, split_index), (split_index, num_tokens)]
+            .into_iter()
+            .filter_map(|(start_index, end_index)| {
+                let local = TokenInterval { start_index, end_index };
+                let text = tokens_text(&tokenized_text, &local).ok()?;
+                let char_interval = get_char_interval(&tokenized_text, &local).ok()?;
+                Some(DocumentChunk {
+                    token_interval: TokenInterval {
+                        start_index: self.token_interval.start_index + start_index,
+                        end_index: self.token_interval.start_index + end_index,
+                    },
+                    char_interval: CharInterval::new(
+                        char_interval.start_pos.map(|pos| pos + char_offset),
+                        char_interval.end_pos.map(|pos| pos + char_offset),
+                    ),
+                    text,
+                    overlap_end_pos: if start_index == 0 { self.overlap_end_pos } else { None },
+                    advance: 0,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Whether a model output that failed to parse looks cut off: a code fence
+/// that is never closed, or unfenced JSON whose brackets are never closed.
+fn looks_truncated(output: &str) -> bool {
+    let trimmed = output.trim();
+    if let Some(fenced) = trimmed.strip_prefix("```") {
+        return !fenced.contains("```");
+    }
+    if !trimmed.starts_with(['{', '[']) {
+        return false;
+    }
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in trimmed.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '[' if !in_string => depth += 1,
+            '}' | ']' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    in_string || depth > 0
+}
+
+/// Flattens the extractions of consecutive chunks, reporting an entity found
+/// in the overlap between two chunks only once.
+///
+/// An extraction starting in a chunk's overlap duplicates an extraction of an
+/// earlier chunk when both have the same class and overlapping spans; the
+/// longer span is kept, as the earlier one may have been cut off at the chunk
+/// boundary.
+fn deduplicate_chunk_overlaps(chunks: &[DocumentChunk], chunk_extractions: Vec<Vec<Extraction>>) -> Vec<Extraction> {
+    let span_len = |e: &Extraction| {
+        e.char_interval
+            .as_ref()
+            .and_then(|ci| Some(ci.end_pos?.saturating_sub(ci.start_pos?)))
+            .unwrap_or(0)
+    };
+    let mut kept: Vec<Extraction> = Vec::new();
+    for (chunk, extractions) in chunks.iter().zip(chunk_extractions) {
+        let earlier_chunks = kept.len();
+        for extraction in extractions {
+            let start_pos = extraction.char_interval.as_ref().and_then(|ci| ci.start_pos);
+            let in_overlap = matches!((start_pos, chunk.overlap_end_pos), (Some(start), Some(end)) if start < end);
+            let duplicate = kept[..earlier_chunks].iter().position(|existing| {
+                existing.extraction_class == extraction.extraction_class && extractions_overlap(existing, &extraction)
+            });
+            match duplicate {
+                Some(index) if in_overlap => {
+                    if span_len(&extraction) > span_len(&kept[index]) {
+                        kept[index] = extraction;
+                    }
+                }
+                _ => kept.push(extraction),
+            }
+        }
+    }
+    kept
+}
+
+/// Converts an aligned resolver extraction into the public data model.
+fn to_data_extraction(e: crate::resolver::data::Extraction) -> Extraction {
+    let token_interval = e.token_interval.map(|ti| TokenInterval {
+        start_index: ti.start_index,
+        end_index: ti.end_index,
+    });
+    let char_interval = e.char_interval.map(|ci| CharInterval {
+        start_pos: Some(ci.start_pos),
+        end_pos: Some(ci.end_pos),
+    });
+    let alignment_status = e.alignment_status.map(|status| match status {
+        crate::resolver::data::AlignmentStatus::MatchExact => crate::data::AlignmentStatus::MatchExact,
+        crate::resolver::data::AlignmentStatus::MatchGreater => crate::data::AlignmentStatus::MatchGreater,
+        crate::resolver::data::AlignmentStatus::MatchLesser => crate::data::AlignmentStatus::MatchLesser,
+        crate::resolver::data::AlignmentStatus::MatchFuzzy => crate::data::AlignmentStatus::MatchFuzzy,
+        crate::resolver::data::AlignmentStatus::MatchSentence => crate::data::AlignmentStatus::MatchSentence,
+    });
+    let mut extraction = Extraction::new(
+        e.extraction_class,
+        e.extraction_text,
+        token_interval,
+        char_interval,
+        alignment_status,
+        Some(e.extraction_index),
+        Some(e.group_index),
+        None,
+        e.attributes.as_ref().and_then(to_data_attributes),
+    );
+    extraction.duplicate = e.duplicate;
+    extraction.edit_distance = e.edit_distance;
+    extraction
+}
+
+/// Converts resolved attributes into the public data model; non-string
+/// values are kept in their JSON form.
+fn to_data_attributes(attributes: &serde_json::Value) -> Option<HashMap<String, AttributeValue>> {
+    let as_text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let attributes: HashMap<String, AttributeValue> = attributes
+        .as_object()?
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| {
+            let value = match value.as_array() {
+                Some(values) => AttributeValue::Multiple(values.iter().map(as_text).collect()),
+                None => AttributeValue::Single(as_text(value)),
+            };
+            (key.clone(), value)
+        })
+        .collect();
+    (!attributes.is_empty()).then_some(attributes)
+}
+
 /// Annotates documents with extractions using a language model.
 pub struct Annotator<L: BaseLanguageModel> {
     language_model: L,
     prompt_generator: QAPromptGenerator,
+    observers: Vec<Box<dyn AnnotationObserver>>,
+    documents_in_flight: usize,
+    max_concurrency: usize,
+    inference_slots: Semaphore,
+    failure_policy: FailurePolicy,
+    checkpoint_store: Option<Box<dyn CheckpointStore>>,
+    merge_strategy: Box<dyn MergeStrategy>,
+    pass_prompting: PassPrompting,
+    self_consistency: Option<SelfConsistency>,
+    ensemble_agreement: Option<Agreement>,
+    max_truncation_splits: usize,
+    max_repair_attempts: usize,
+    keep_reasoning: bool,
+    chunk_overlap: ChunkOverlap,
+    relevance_filters: Vec<Box<dyn RelevanceFilter>>,
+    verifier: Option<Box<dyn ExtractionVerifier>>,
+    enable_fuzzy_alignment: bool,
+    fuzzy_alignment_threshold: f64,
+    accept_match_lesser: bool,
 }
 
 impl<L: BaseLanguageModel> Annotator<L> {
@@ -134,12 +478,210 @@ impl<L: BaseLanguageModel> Annotator<L> {
         Self {
             language_model,
             prompt_generator,
+            observers: Vec::new(),
+            documents_in_flight: 1,
+            max_concurrency: 1,
+            inference_slots: Semaphore::new(1),
+            failure_policy: FailurePolicy::default(),
+            checkpoint_store: None,
+            merge_strategy: Box::new(FirstWins),
+            pass_prompting: PassPrompting::default(),
+            self_consistency: None,
+            ensemble_agreement: None,
+            max_truncation_splits: 2,
+            max_repair_attempts: 0,
+            keep_reasoning: false,
+            chunk_overlap: ChunkOverlap::None,
+            relevance_filters: Vec::new(),
+            verifier: None,
+            enable_fuzzy_alignment: false,
+            fuzzy_alignment_threshold: FUZZY_ALIGNMENT_MIN_THRESHOLD,
+            accept_match_lesser: false,
         }
     }
 
+    /// Registers an observer that is notified of batch, chunk, document and
+    /// pass progress on every subsequent run.
+    pub fn with_observer(mut self, observer: impl AnnotationObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Sets how many documents are annotated concurrently (default 1).
+    ///
+    /// Memory use of a run is bounded by the documents in flight rather than
+    /// by the size of the corpus.
+    pub fn with_documents_in_flight(mut self, documents_in_flight: usize) -> Self {
+        self.documents_in_flight = documents_in_flight.max(1);
+        self
+    }
+
+    /// Sets how many language model requests may be in flight at once across
+    /// all documents, batches and passes (default 1).
+    ///
+    /// Batches of a document and its extraction passes are sent concurrently
+    /// up to this limit; results are still assembled in chunk and pass order,
+    /// so the output does not depend on completion order.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self.inference_slots = Semaphore::new(self.max_concurrency);
+        self
+    }
+
+    /// Sets how chunk failures are handled (default [`FailurePolicy::FailFast`]).
+    ///
+    /// Under the other policies the run continues and every chunk that could
+    /// not be annotated is listed in [`AnnotatedDocument::chunk_failures`].
+    pub fn with_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Saves every completed chunk to `checkpoint_store` and skips chunks
+    /// already recorded there, so an interrupted run can be resumed by
+    /// running it again with the same store.
+    pub fn with_checkpoint_store(mut self, checkpoint_store: impl CheckpointStore + 'static) -> Self {
+        self.checkpoint_store = Some(Box::new(checkpoint_store));
+        self
+    }
+
+    /// Sets how the extractions of multiple extraction passes are merged
+    /// (default [`FirstWins`]).
+    pub fn with_merge_strategy(mut self, merge_strategy: impl MergeStrategy + 'static) -> Self {
+        self.merge_strategy = Box::new(merge_strategy);
+        self
+    }
+
+    /// Sets how extraction passes after the first one are prompted.
+    pub fn with_pass_prompting(mut self, pass_prompting: PassPrompting) -> Self {
+        self.pass_prompting = pass_prompting;
+        self
+    }
+
+    /// Samples every chunk `self_consistency.samples` times per pass and
+    /// keeps only extractions that enough samples agree on.
+    ///
+    /// The first sample of a chunk is subject to the failure policy; later
+    /// samples that fail count as samples without extractions.
+    pub fn with_self_consistency(mut self, self_consistency: SelfConsistency) -> Self {
+        self.self_consistency = Some(self_consistency);
+        self
+    }
+
+    /// Treats every output the model returns for a chunk as the answer of one
+    /// ensemble member, such as an [`EnsembleModel`](crate::ensemble::EnsembleModel),
+    /// and keeps the extractions enough members agree on.
+    ///
+    /// Members agree on an extraction when they found the same class with
+    /// overlapping spans. Each kept extraction lists the agreeing members in
+    /// `model_ids`, and the fraction of members that agree becomes its
+    /// confidence.
+    pub fn with_ensemble_agreement(mut self, agreement: Agreement) -> Self {
+        self.ensemble_agreement = Some(agreement);
+        self
+    }
+
+    /// Sets how many times a chunk whose output was truncated by the model's
+    /// output token limit is split in two and retried (default 2, so down to
+    /// quarters). A part that is still truncated after the last split keeps
+    /// the items lenient parsing salvaged from its output, if any. Zero
+    /// disables splitting, so salvaged items are kept right away and chunks
+    /// without any fail.
+    ///
+    /// Truncation is detected from the provider's finish reason or from an
+    /// output that fails to parse and ends inside an unclosed structure.
+    pub fn with_max_truncation_splits(mut self, max_truncation_splits: usize) -> Self {
+        self.max_truncation_splits = max_truncation_splits;
+        self
+    }
+
+    /// Sends output that fails to parse back to the model together with the
+    /// parser error, asking for a corrected version, up to `max_repair_attempts`
+    /// times per chunk (default 0, no repair). Attempts are recorded in
+    /// [`AnnotatedDocument::chunk_diagnostics`].
+    pub fn with_max_repair_attempts(mut self, max_repair_attempts: usize) -> Self {
+        self.max_repair_attempts = max_repair_attempts;
+        self
+    }
+
+    /// Keeps the reasoning of reasoning models, given in `<think>` blocks or
+    /// a separate reasoning field, in [`AnnotatedDocument::chunk_diagnostics`].
+    /// Only the answer after the reasoning is ever parsed.
+    pub fn with_reasoning_traces(mut self, keep_reasoning: bool) -> Self {
+        self.keep_reasoning = keep_reasoning;
+        self
+    }
+
+    /// Makes each chunk repeat the end of the previous one so entities that
+    /// straddle a chunk boundary are seen whole. Extractions found twice in
+    /// the overlap are reported once.
+    pub fn with_chunk_overlap(mut self, chunk_overlap: ChunkOverlap) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    /// Adds a filter that decides whether a chunk is worth extracting from.
+    ///
+    /// Filters run before any extraction pass, in registration order, and a
+    /// chunk is only sent to the language model if every filter accepts it.
+    /// Skipped chunks are listed in [`AnnotatedDocument::skipped_chunks`]. A
+    /// filter that fails is reported to observers and the chunk is kept.
+    pub fn with_relevance_filter(mut self, relevance_filter: impl RelevanceFilter + 'static) -> Self {
+        self.relevance_filters.push(Box::new(relevance_filter));
+        self
+    }
+
+    /// Aligns extractions without an exact match by token overlap and then by
+    /// character edit distance, both reported as `MatchFuzzy` (default off).
+    /// See [`Annotator::with_fuzzy_alignment_threshold`].
+    pub fn with_fuzzy_alignment(mut self, enable_fuzzy_alignment: bool) -> Self {
+        self.enable_fuzzy_alignment = enable_fuzzy_alignment;
+        self
+    }
+
+    /// Sets the share of extraction tokens a fuzzy match must cover (default
+    /// [`FUZZY_ALIGNMENT_MIN_THRESHOLD`]). An edit-distance match may differ
+    /// in at most one minus this share of its characters.
+    pub fn with_fuzzy_alignment_threshold(mut self, fuzzy_alignment_threshold: f64) -> Self {
+        self.fuzzy_alignment_threshold = fuzzy_alignment_threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Aligns extractions of which only a contiguous run of at least half the
+    /// tokens is found in the chunk, reported as `MatchLesser` (default off).
+    pub fn with_match_lesser(mut self, accept_match_lesser: bool) -> Self {
+        self.accept_match_lesser = accept_match_lesser;
+        self
+    }
+
+    /// Verifies the extractions of every document once its passes are
+    /// resolved and merged, see [`crate::verification::Verifier`]. Under
+    /// [`FailurePolicy::FailFast`] a failed verification fails the document;
+    /// otherwise the extractions are kept unverified and the error is recorded
+    /// in [`AnnotatedDocument::verification_error`].
+    pub fn with_verifier(mut self, verifier: impl ExtractionVerifier + 'static) -> Self {
+        self.verifier = Some(Box::new(verifier));
+        self
+    }
+
+    fn notify(&self, event: impl Fn(&dyn AnnotationObserver)) {
+        for observer in &self.observers {
+            event(observer.as_ref());
+        }
+    }
+
+    /// Reports a finished or skipped chunk and the progress it makes.
+    fn notify_chunk_done(&self, document_id: &str, chunk: &DocumentChunk, num_extractions: usize) {
+        self.notify(|o| {
+            o.on_chunk_done(document_id, &chunk.char_interval, num_extractions);
+            o.on_chars_processed(chunk.advance);
+        });
+    }
+
     /// Annotates a sequence of documents with NLP extractions.
     /// Breaks documents into chunks, processes them into prompts and performs
     /// batched inference, mapping annotated extractions back to the original document.
+    #[allow(clippy::too_many_arguments)]
     pub async fn annotate_documents(
         &self,
         documents: Vec<Document>,
@@ -148,262 +690,905 @@ impl<L: BaseLanguageModel> Annotator<L> {
         batch_length: usize,
         debug: bool,
         extraction_passes: usize,
-        extra_args: Option<HashMap<String, String>>,
+        _extra_args: Option<HashMap<String, String>>,
     ) -> Result<Vec<AnnotatedDocument>, InferenceOutputError> {
-        if extraction_passes == 1 {
-            self.annotate_documents_single_pass(documents, resolver, max_char_buffer, batch_length, debug, extra_args)
-                .await
-        } else {
-            self.annotate_documents_sequential_passes(
-                documents,
-                resolver,
-                max_char_buffer,
-                batch_length,
-                debug,
-                extraction_passes,
-                extra_args,
-            )
+        let total_chars: usize = documents.iter().map(|d| d.text.chars().count()).sum();
+        let run = AnnotationRun::new(resolver, max_char_buffer, batch_length, debug, extraction_passes);
+        self.annotate_with_run(stream::iter(documents), run, Some(total_chars * extraction_passes))
+            .try_collect()
             .await
-        }
     }
 
-    /// Single-pass annotation logic (original implementation).
-    async fn annotate_documents_single_pass(
-        &self,
-        documents: Vec<Document>,
-        resolver: &dyn AbstractResolver,
+    /// Annotates a stream of documents, yielding each annotated document as
+    /// soon as it and every document before it are complete.
+    ///
+    /// Documents are pulled lazily and only the documents in flight (see
+    /// [`Annotator::with_documents_in_flight`]) are held in memory, so this
+    /// scales to corpora that do not fit in memory. Output order matches input
+    /// order. Wrap an `Iterator` with `futures::stream::iter` to annotate it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn annotate_stream<'a, S>(
+        &'a self,
+        documents: S,
+        resolver: &'a dyn AbstractResolver,
         max_char_buffer: usize,
         batch_length: usize,
         debug: bool,
+        extraction_passes: usize,
         _extra_args: Option<HashMap<String, String>>,
-    ) -> Result<Vec<AnnotatedDocument>, InferenceOutputError> {
-        println!("Starting document annotation.");
-        let mut docs: Vec<Document> = documents;
-        let chunk_iter = document_chunk_iterator(docs.clone(), max_char_buffer, true)
+    ) -> impl Stream<Item = Result<AnnotatedDocument, InferenceOutputError>> + 'a
+    where
+        S: Stream<Item = Document> + 'a,
+    {
+        let run = AnnotationRun::new(resolver, max_char_buffer, batch_length, debug, extraction_passes);
+        self.annotate_with_run(documents, run, None)
+    }
+
+    /// Chunks the documents and renders every prompt a run with the same
+    /// settings would send, without calling the language model.
+    ///
+    /// Relevance filters are not applied, since they may call a model
+    /// themselves, so the report is an upper bound when filters are set. With
+    /// [`PassPrompting::find_missed`], later passes are rendered without the
+    /// earlier extractions they would list.
+    pub fn dry_run(
+        &self,
+        documents: Vec<Document>,
+        max_char_buffer: usize,
+        extraction_passes: usize,
+    ) -> Result<DryRunReport, InferenceOutputError> {
+        let samples = self.self_consistency.as_ref().map_or(1, |sc| sc.samples);
+        let mut report = DryRunReport::default();
+        for mut document in documents {
+            let document_id = document.document_id();
+            let tokenized_text = document.tokenized_text().clone();
+            let chunks = ChunkIterator::new(&tokenized_text, max_char_buffer, None)
+                .with_overlap(self.chunk_overlap)
+                .map(|chunk| DocumentChunk::new(&tokenized_text, chunk))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| InferenceOutputError::new(e.to_string()))?;
+            let batch: Vec<(&DocumentChunk, &[Extraction])> = chunks.iter().map(|chunk| (chunk, &[][..])).collect();
+
+            report.num_documents += 1;
+            report.num_chunks += chunks.len();
+            for pass_index in 0..extraction_passes {
+                for sample_index in 0..samples {
+                    let prompts =
+                        self.render_prompts(&batch, document.additional_context.as_deref(), pass_index, sample_index);
+                    for (chunk, prompt) in chunks.iter().zip(prompts) {
+                        report.prompts.push(DryRunPrompt {
+                            document_id: document_id.clone(),
+                            pass_index,
+                            sample_index,
+                            char_interval: chunk.char_interval.clone(),
+                            estimated_prompt_tokens: estimate_tokens(&prompt),
+                            estimated_output_tokens: estimate_tokens(&chunk.text),
+                            prompt,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn annotate_with_run<'a, S>(
+        &'a self,
+        documents: S,
+        run: AnnotationRun<'a>,
+        total_chars: Option<usize>,
+    ) -> impl Stream<Item = Result<AnnotatedDocument, InferenceOutputError>> + 'a
+    where
+        S: Stream<Item = Document> + 'a,
+    {
+        // The run starts when the stream is first polled, not when it is built.
+        let started = stream::once(async move {
+            self.notify(|o| o.on_run_start(total_chars));
+            None
+        });
+        let run = Arc::new(run);
+        let mut visited_ids = HashSet::new();
+        let annotated = documents
+            .map(move |mut document| {
+                let run = Arc::clone(&run);
+                let document_id = document.document_id();
+                let repeated = !visited_ids.insert(document_id.clone());
+                async move {
+                    if repeated {
+                        let error = DocumentRepeatError(format!("Document id {} is already visited.", document_id));
+                        return Err(InferenceOutputError::new(error.to_string()));
+                    }
+                    self.annotate_document(document, &run).await
+                }
+            })
+            .buffered(self.documents_in_flight)
+            .inspect(move |result| match result {
+                Ok(annotated_document) => self.notify(|o| o.on_document_done(annotated_document)),
+                Err(e) => self.notify(|o| o.on_error(e)),
+            });
+        let finished = stream::once(async move {
+            progress::print_extraction_complete();
+            self.notify(|o| o.on_run_done());
+            None
+        });
+        started
+            .chain(annotated.map(Some))
+            .chain(finished)
+            .filter_map(future::ready)
+    }
+
+    /// Chunks one document, runs every extraction pass over its chunks and
+    /// merges the passes into a single annotated document.
+    async fn annotate_document(
+        &self,
+        mut document: Document,
+        run: &AnnotationRun<'_>,
+    ) -> Result<AnnotatedDocument, InferenceOutputError> {
+        let document_id = document.document_id();
+        let tokenized_text = document.tokenized_text().clone();
+        let mut chunks = ChunkIterator::new(&tokenized_text, run.max_char_buffer, None)
+            .with_overlap(self.chunk_overlap)
+            .map(|chunk| DocumentChunk::new(&tokenized_text, chunk))
+            .collect::<Result<Vec<_>, _>>()
             .map_err(|e| InferenceOutputError::new(e.to_string()))?;
-        let mut doc_iter = docs.iter_mut();
-        let mut curr_document = doc_iter.next();
-        if curr_document.is_none() {
-            println!("No documents to process.");
-            return Ok(vec![]);
-        }
-        let mut annotated_extractions: Vec<Extraction> = Vec::new();
-        let batches = make_batches_of_textchunk(chunk_iter.into_iter(), batch_length);
-        let model_info = None; // progress::get_model_info(&self.language_model);
-        let mut chars_processed = 0;
-        let mut annotated_documents = Vec::new();
-
-        for (index, mut batch) in batches.into_iter().enumerate() {
-            println!("Processing batch {} with length {}", index, batch.len());
-            let batch_prompts: Vec<String> = batch
-                .iter_mut()
-                .map(|text_chunk| {
-                    self.prompt_generator
-                        .render(&text_chunk.chunk_text().unwrap_or_default())
+        let mut covered = 0;
+        for chunk in &mut chunks {
+            let end = chunk.char_interval.end_pos.unwrap_or(covered).max(covered);
+            chunk.advance = end - covered;
+            covered = end;
+        }
+        if let Some(last) = chunks.last_mut() {
+            last.advance += document.text.chars().count().saturating_sub(covered);
+        }
+        let (chunks, skipped_chunks) = self.filter_relevant_chunks(&document_id, chunks, run).await;
+
+        // Every pass reuses the same chunks. Independent (pass, batch) pairs
+        // run concurrently and are reassembled in order; when later passes
+        // are told what earlier passes found, the passes run one after another.
+        let pass_groups: Vec<Vec<usize>> = if self.pass_prompting.find_missed {
+            (0..run.extraction_passes).map(|pass_index| vec![pass_index]).collect()
+        } else {
+            vec![(0..run.extraction_passes).collect()]
+        };
+        let mut pass_chunk_extractions = vec![vec![Vec::new(); chunks.len()]; run.extraction_passes];
+        let mut chunk_extractions: Vec<Vec<Extraction>> = vec![Vec::new(); chunks.len()];
+        let mut chunk_failures = Vec::new();
+        let mut chunk_diagnostics = Vec::new();
+        for pass_group in pass_groups {
+            let batches = pass_group.iter().copied().flat_map(|pass_index| {
+                (0..chunks.len())
+                    .step_by(run.batch_length)
+                    .map(move |start| (pass_index, start))
+            });
+            let batches_per_pass = chunks.len().div_ceil(run.batch_length);
+            let mut remaining_batches = vec![batches_per_pass; run.extraction_passes];
+            if batches_per_pass == 0 {
+                for &pass_index in &pass_group {
+                    self.notify(|o| o.on_pass_done(&document_id, pass_index, run.extraction_passes));
+                }
+            }
+            let prior = &chunk_extractions;
+            let batch_outcomes: Vec<_> = stream::iter(batches)
+                .map(|(pass_index, start)| {
+                    let document_id = &document_id;
+                    let additional_context = document.additional_context.as_deref();
+                    let end = (start + run.batch_length).min(chunks.len());
+                    let (batch, prior) = (&chunks[start..end], &prior[start..end]);
+                    async move {
+                        let outcomes = self
+                            .annotate_batch(document_id, additional_context, batch, prior, pass_index, run)
+                            .await?;
+                        Ok::<_, InferenceOutputError>((pass_index, start, outcomes))
+                    }
                 })
-                .collect();
+                .buffered(self.max_concurrency)
+                .inspect_ok(|(pass_index, _, _)| {
+                    // Batches come back in order, so a pass is complete with its last batch.
+                    remaining_batches[*pass_index] -= 1;
+                    if remaining_batches[*pass_index] == 0 {
+                        self.notify(|o| o.on_pass_done(&document_id, *pass_index, run.extraction_passes));
+                    }
+                })
+                .try_collect()
+                .await?;
 
-            // Show what we're currently processing
-            if debug {
-                let batch_size: usize = batch
-                    .iter_mut()
-                    .map(|chunk| chunk.chunk_text().unwrap_or_default().len())
-                    .sum();
-                let _desc = progress::format_extraction_progress(model_info, Some(batch_size), Some(chars_processed));
-                // progress bar description update not implemented
-            }
-
-            // infer is async
-            let batch_scored_outputs = self.language_model.infer(&batch_prompts, None).await?;
-
-            // Update total processed
-            if debug {
-                for mut chunk in batch.clone() {
-                    if let Some(_document_text) = chunk.document_text() {
-                        if let Ok(char_interval) = chunk.char_interval() {
-                            let start = char_interval.start_pos.unwrap_or(0);
-                            let end = char_interval.end_pos.unwrap_or(0);
-                            chars_processed += end - start;
+            for (pass_index, start, outcomes) in batch_outcomes {
+                for (chunk_index, outcome) in (start..).zip(outcomes) {
+                    chunk_diagnostics.extend(outcome.diagnostics);
+                    match outcome.result {
+                        Ok(extractions) => {
+                            chunk_extractions[chunk_index].extend(extractions.iter().cloned());
+                            pass_chunk_extractions[pass_index][chunk_index] = extractions;
                         }
+                        Err(failure) => chunk_failures.push(failure),
                     }
                 }
-                let batch_size: usize = batch
-                    .iter_mut()
-                    .map(|chunk| chunk.chunk_text().unwrap_or_default().len())
-                    .sum();
-                let _desc = progress::format_extraction_progress(model_info, Some(batch_size), Some(chars_processed));
-                // progress bar description update not implemented
-            }
-
-            for (text_chunk, scored_outputs) in batch.into_iter().zip(batch_scored_outputs.iter()) {
-                println!("Processing chunk: {:?}", text_chunk);
-                if scored_outputs.is_empty() {
-                    println!("No scored outputs for chunk with ID {:?}.", text_chunk.document_id());
-                    return Err(InferenceOutputError::new("No scored outputs from language model."));
-                }
-                let current_doc_id = curr_document.as_mut().map(|d| d.document_id());
-                while current_doc_id != text_chunk.document_id() {
-                    let doc_id = curr_document.as_mut().map(|d| d.document_id());
-                    println!("Completing annotation for document ID {:?}.", doc_id);
-                    let annotated_doc = AnnotatedDocument::new(
-                        curr_document.as_mut().map(|d| Some(d.document_id())).unwrap_or(None),
-                        Some(annotated_extractions.clone()),
-                        Some(curr_document.as_mut().map(|d| d.text.clone()).unwrap_or_default()),
-                    );
-                    annotated_documents.push(annotated_doc);
-                    annotated_extractions.clear();
-                    curr_document = doc_iter.next();
-                    assert!(
-                        curr_document.is_some(),
-                        "Document should be defined for chunk per document_chunk_iterator specifications."
-                    );
+            }
+        }
+        let all_pass_extractions: Vec<Vec<Extraction>> = pass_chunk_extractions
+            .into_iter()
+            .map(|extractions| deduplicate_chunk_overlaps(&chunks, extractions))
+            .collect();
+
+        let merged_extractions = self.merge_strategy.merge(&all_pass_extractions);
+        if run.debug && run.extraction_passes > 1 {
+            let total_extractions: usize = all_pass_extractions.iter().map(|extractions| extractions.len()).sum();
+            println!(
+                "Document {}: Merged {} extractions from {} passes into {} non-overlapping extractions.",
+                document_id,
+                total_extractions,
+                run.extraction_passes,
+                merged_extractions.len(),
+            );
+        }
+        let mut annotated_document =
+            AnnotatedDocument::new(Some(document_id), Some(merged_extractions), Some(document.text));
+        annotated_document.chunk_failures = chunk_failures;
+        annotated_document.skipped_chunks = skipped_chunks;
+        annotated_document.chunk_diagnostics = chunk_diagnostics;
+        if let Some(verifier) = &self.verifier {
+            match verifier.verify_document(&mut annotated_document).await {
+                Ok(()) => {}
+                Err(e) if self.failure_policy == FailurePolicy::FailFast => return Err(e),
+                Err(e) => {
+                    let error = InferenceOutputError::new(format!(
+                        "Document {}: verification failed: {}",
+                        annotated_document.document_id(),
+                        e
+                    ));
+                    self.notify(|o| o.on_error(&error));
+                    annotated_document.verification_error = Some(e.message);
                 }
-                let top_inference_result = scored_outputs[0].output.clone().unwrap_or_default();
-                println!("Top inference result: {}", top_inference_result);
+            }
+        }
+        Ok(annotated_document)
+    }
 
-                let annotated_chunk_extractions = resolver.resolve(&top_inference_result, debug);
+    /// Runs the relevance filters over `chunks`, returning the chunks to
+    /// extract from and the positions of the skipped ones.
+    async fn filter_relevant_chunks(
+        &self,
+        document_id: &str,
+        chunks: Vec<DocumentChunk>,
+        run: &AnnotationRun<'_>,
+    ) -> (Vec<DocumentChunk>, Vec<CharInterval>) {
+        if self.relevance_filters.is_empty() {
+            return (chunks, Vec::new());
+        }
+        let relevant: Vec<bool> = stream::iter(&chunks)
+            .map(|chunk| self.is_relevant(&chunk.text))
+            .buffered(self.max_concurrency)
+            .collect()
+            .await;
 
-                // Get all values that need mutable access first
-                let mut text_chunk_for_text = text_chunk.clone();
-                let chunk_text = text_chunk_for_text.chunk_text().unwrap_or_default();
+        let mut kept = Vec::new();
+        let mut skipped = Vec::new();
+        for (chunk, relevant) in chunks.into_iter().zip(relevant) {
+            if relevant {
+                kept.push(chunk);
+                continue;
+            }
+            self.notify(|o| o.on_chunk_skipped(document_id, &chunk.char_interval));
+            // Skipped chunks still count towards the progress of every pass.
+            for _ in 0..run.extraction_passes {
+                self.notify_chunk_done(document_id, &chunk, 0);
+            }
+            skipped.push(chunk.char_interval);
+        }
+        if run.debug && !skipped.is_empty() {
+            println!(
+                "Document {}: Skipped {} of {} chunks judged irrelevant.",
+                document_id,
+                skipped.len(),
+                skipped.len() + kept.len(),
+            );
+        }
+        (kept, skipped)
+    }
 
-                let mut text_chunk_for_char = text_chunk.clone();
-                let char_offset = match text_chunk_for_char.char_interval() {
-                    Ok(ci) => ci.start_pos.unwrap_or(0),
-                    Err(_) => 0,
-                };
+    /// Whether every relevance filter accepts `chunk_text`. A filter error
+    /// keeps the chunk, since skipping it would silently lose extractions.
+    async fn is_relevant(&self, chunk_text: &str) -> bool {
+        for relevance_filter in &self.relevance_filters {
+            match relevance_filter.is_relevant(chunk_text).await {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(e) => self.notify(|o| o.on_error(&e)),
+            }
+        }
+        true
+    }
 
-                // Get immutable values
-                let token_offset = text_chunk.token_interval.start_index;
+    /// Sends one batch of chunks to the language model, then resolves and
+    /// aligns the top output of each chunk.
+    ///
+    /// Returns one outcome per chunk; failed chunks are retried according to
+    /// the failure policy and only abort the run under
+    /// [`FailurePolicy::FailFast`]. Chunks found in the checkpoint store are
+    /// not sent again.
+    async fn annotate_batch(
+        &self,
+        document_id: &str,
+        additional_context: Option<&str>,
+        chunks: &[DocumentChunk],
+        prior_extractions: &[Vec<Extraction>],
+        pass_index: usize,
+        run: &AnnotationRun<'_>,
+    ) -> Result<Vec<ChunkOutcome>, InferenceOutputError> {
+        let checkpoints: Vec<Option<Vec<Extraction>>> = chunks
+            .iter()
+            .map(|chunk| {
+                let store = self.checkpoint_store.as_ref()?;
+                store.load(&ChunkKey::new(document_id, &chunk.char_interval, pass_index))
+            })
+            .collect();
+        let batch: Vec<(&DocumentChunk, &[Extraction])> = chunks
+            .iter()
+            .zip(prior_extractions)
+            .zip(&checkpoints)
+            .filter(|(_, checkpoint)| checkpoint.is_none())
+            .map(|((chunk, prior), _)| (chunk, prior.as_slice()))
+            .collect();
+        let mut fresh_outcomes = if batch.is_empty() {
+            Vec::new()
+        } else {
+            self.annotate_pending(document_id, additional_context, &batch, pass_index, run)
+                .await?
+        }
+        .into_iter();
 
-                // For demonstration, use default values for fuzzy alignment
-                let enable_fuzzy_alignment = false;
-                let fuzzy_alignment_threshold = 0.75;
-                let accept_match_lesser = false;
+        let mut outcomes = Vec::with_capacity(chunks.len());
+        for (chunk, checkpoint) in chunks.iter().zip(checkpoints) {
+            match checkpoint {
+                Some(extractions) => {
+                    self.notify_chunk_done(document_id, chunk, extractions.len());
+                    outcomes.push(ChunkOutcome {
+                        result: Ok(extractions),
+                        diagnostics: None,
+                    });
+                }
+                None => outcomes.extend(fresh_outcomes.next()),
+            }
+        }
+        Ok(outcomes)
+    }
 
-                let aligned_extractions = match &annotated_chunk_extractions {
-                    Ok(extractions) => resolver.align(
-                        extractions,
-                        &chunk_text,
-                        token_offset,
-                        Some(char_offset),
-                        enable_fuzzy_alignment,
-                        fuzzy_alignment_threshold,
-                        accept_match_lesser,
-                    ),
-                    Err(_) => Vec::new(),
+    /// Annotates the chunks of a batch that have no checkpoint yet, each
+    /// paired with the extractions earlier passes found in it.
+    async fn annotate_pending(
+        &self,
+        document_id: &str,
+        additional_context: Option<&str>,
+        batch: &[(&DocumentChunk, &[Extraction])],
+        pass_index: usize,
+        run: &AnnotationRun<'_>,
+    ) -> Result<Vec<ChunkOutcome>, InferenceOutputError> {
+        let batch_index = run.batch_counter.fetch_add(1, Ordering::Relaxed);
+        let batch_prompts = self.render_prompts(batch, additional_context, pass_index, 0);
+        let inference_kwargs = self.inference_kwargs_for_pass(pass_index);
+        let batch_chars = batch.iter().map(|(chunk, _)| chunk.text.chars().count()).sum();
+        self.notify(|o| o.on_batch_start(batch_index, batch.len(), batch_chars));
+
+        // The extra self-consistency samples are drawn alongside the main one.
+        let (main_outputs, extra_samples) = futures::join!(
+            self.infer_limited(&batch_prompts, inference_kwargs.clone()),
+            self.sample_again(batch, additional_context, pass_index, run),
+        );
+        let mut extra_samples = extra_samples.into_iter();
+        let priors: Vec<&[Extraction]> = batch.iter().map(|(_, prior)| *prior).collect();
+        let batch: Vec<&DocumentChunk> = batch.iter().map(|(chunk, _)| *chunk).collect();
+
+        let max_retries = match self.failure_policy {
+            FailurePolicy::FailFast => 0,
+            FailurePolicy::SkipChunk => 0,
+            FailurePolicy::RetryChunk { max_retries } => max_retries,
+        };
+        let batch_scored_outputs = match main_outputs {
+            Ok(mut outputs) => {
+                outputs.resize(batch.len(), Vec::new());
+                outputs.into_iter().map(Ok).collect()
+            }
+            Err(e) if self.failure_policy == FailurePolicy::FailFast => return Err(e),
+            // Re-send the chunks one by one so a single bad chunk does not
+            // take the rest of the batch down with it.
+            Err(e) if batch.len() > 1 => {
+                self.notify(|o| o.on_error(&e));
+                let mut outputs = Vec::with_capacity(batch.len());
+                for prompt in &batch_prompts {
+                    outputs.push(self.infer_chunk(prompt, inference_kwargs.clone()).await);
+                }
+                outputs
+            }
+            Err(e) => vec![Err(e)],
+        };
+
+        let mut outcomes = Vec::with_capacity(batch.len());
+        for (((chunk, prior), prompt), scored_outputs) in
+            batch.iter().zip(priors).zip(&batch_prompts).zip(batch_scored_outputs)
+        {
+            let mut diagnostics = ChunkDiagnostics::new(chunk.char_interval.clone(), pass_index);
+            let mut outcome = self.resolve_chunk(chunk, scored_outputs, run, &mut diagnostics);
+            if let Err(failure) = &mut outcome
+                && failure.error_kind == ChunkErrorKind::Truncated
+            {
+                let request = SplitRequest {
+                    prior,
+                    additional_context,
+                    pass_index,
+                    inference_kwargs: &inference_kwargs,
                 };
-                annotated_extractions.extend(aligned_extractions.into_iter().map(|e| {
-                    let token_interval = e.token_interval.map(|ti| crate::tokenizer::TokenInterval {
-                        start_index: ti.start_index,
-                        end_index: ti.end_index,
-                    });
-                    let char_interval = e.char_interval.map(|ci| crate::data::CharInterval {
-                        start_pos: Some(ci.start_pos),
-                        end_pos: Some(ci.end_pos),
-                    });
-                    let alignment_status = e.alignment_status.map(|status| match status {
-                        crate::resolver::data::AlignmentStatus::MatchExact => crate::data::AlignmentStatus::MatchExact,
-                        crate::resolver::data::AlignmentStatus::MatchLesser => {
-                            crate::data::AlignmentStatus::MatchLesser
-                        }
-                        crate::resolver::data::AlignmentStatus::MatchFuzzy => crate::data::AlignmentStatus::MatchFuzzy,
+                let salvaged = failure.salvaged.take();
+                let failure = failure.clone();
+                outcome = self
+                    .annotate_split(
+                        chunk,
+                        &request,
+                        self.max_truncation_splits,
+                        salvaged,
+                        run,
+                        &mut diagnostics,
+                    )
+                    .await
+                    .map_err(|sub_failure| {
+                        let mut failure = failure.clone();
+                        failure.message =
+                            format!("{} Splitting the chunk did not help: {}", failure.message, sub_failure);
+                        failure
                     });
-                    crate::data::Extraction::new(
-                        e.extraction_class.clone(),
-                        e.extraction_text.clone(),
-                        token_interval,
-                        char_interval,
-                        alignment_status,
-                        Some(e.extraction_index),
-                        Some(e.group_index),
-                        None,
-                        None,
+            }
+            if let Err(failure) = &outcome
+                && failure.error_kind == ChunkErrorKind::Parse
+            {
+                outcome = self
+                    .repair_chunk(chunk, prompt, failure.clone(), &inference_kwargs, run, &mut diagnostics)
+                    .await;
+            }
+            let mut attempt = 0;
+            while outcome
+                .as_ref()
+                .is_err_and(|failure| failure.error_kind != ChunkErrorKind::Truncated)
+                && attempt < max_retries
+            {
+                attempt += 1;
+                if run.debug {
+                    println!(
+                        "Retrying chunk {:?} of document {} (attempt {}/{}).",
+                        chunk.char_interval, document_id, attempt, max_retries
+                    );
+                }
+                let scored_outputs = self.infer_chunk(prompt, inference_kwargs.clone()).await;
+                outcome = self.resolve_chunk(chunk, scored_outputs, run, &mut diagnostics);
+            }
+            let extra = extra_samples.next().unwrap_or_default();
+            if let (Ok(extractions), Some(self_consistency)) = (&mut outcome, &self.self_consistency) {
+                let mut samples = vec![std::mem::take(extractions)];
+                samples.extend(extra);
+                *extractions = vote_overlapping(&samples, self_consistency.min_votes);
+            }
+
+            if let (Ok(extractions), Some(store)) = (&outcome, &self.checkpoint_store) {
+                store
+                    .save(
+                        &ChunkKey::new(document_id, &chunk.char_interval, pass_index),
+                        extractions,
                     )
-                }));
+                    .map_err(|e| InferenceOutputError::new(e.to_string()))?;
+            }
+            let num_extractions = outcome.as_ref().map_or(0, |extractions| extractions.len());
+            if let Err(failure) = &outcome {
+                let error = InferenceOutputError::new(format!("Document {}: {}", document_id, failure));
+                if self.failure_policy == FailurePolicy::FailFast {
+                    return Err(error);
+                }
+                self.notify(|o| o.on_error(&error));
+            }
+            self.notify_chunk_done(document_id, chunk, num_extractions);
+            outcomes.push(ChunkOutcome {
+                result: outcome,
+                diagnostics: (!diagnostics.is_empty()).then_some(diagnostics),
+            });
+        }
+        Ok(outcomes)
+    }
+
+    /// Sends output that failed to parse back to the model with the parser
+    /// error and asks for a corrected version, up to the configured number of
+    /// repair attempts. Every attempt is recorded in `diagnostics`.
+    async fn repair_chunk(
+        &self,
+        chunk: &DocumentChunk,
+        prompt: &str,
+        mut failure: ChunkFailure,
+        inference_kwargs: &Option<HashMap<String, serde_json::Value>>,
+        run: &AnnotationRun<'_>,
+        diagnostics: &mut ChunkDiagnostics,
+    ) -> Result<Vec<Extraction>, ChunkFailure> {
+        for _ in 0..self.max_repair_attempts {
+            let Some(output) = failure
+                .raw_output
+                .clone()
+                .filter(|_| failure.error_kind == ChunkErrorKind::Parse)
+            else {
+                break;
+            };
+            let repair_prompt = self.repair_prompt(prompt, &output, &failure.message);
+            diagnostics.repair_attempts.push(RepairAttempt {
+                output,
+                error: failure.message.clone(),
+            });
+            let scored_outputs = self.infer_chunk(&repair_prompt, inference_kwargs.clone()).await;
+            let repaired_output = scored_outputs
+                .as_ref()
+                .ok()
+                .and_then(|outputs| outputs.first())
+                .and_then(|top| top.output.clone());
+            match self.resolve_chunk(chunk, scored_outputs, run, diagnostics) {
+                Ok(extractions) => {
+                    diagnostics.repaired_output = repaired_output;
+                    return Ok(extractions);
+                }
+                Err(next_failure) => failure = next_failure,
             }
         }
-        progress::print_extraction_complete();
-        if let Some(curr_document) = curr_document {
-            println!("Finalizing annotation for document ID {}.", curr_document.document_id());
-            let annotated_doc = AnnotatedDocument::new(
-                Some(curr_document.document_id()),
-                Some(annotated_extractions.clone()),
-                Some(curr_document.text.clone()),
+        Err(failure)
+    }
+
+    /// Asks the model to correct `output`, which it gave for `prompt` and which
+    /// failed to parse with `error`.
+    fn repair_prompt(&self, prompt: &str, output: &str, error: &str) -> String {
+        let format = match self.prompt_generator.format_type {
+            crate::prompting::FormatType::YAML => "YAML",
+            crate::prompting::FormatType::JSON => "JSON",
+        };
+        format!(
+            "{}{}\n\nThe answer above could not be parsed: {}\nReply with the corrected {} only, keeping the same extractions.\n",
+            prompt, output, error, format
+        )
+    }
+
+    /// Annotates a chunk whose output was truncated by splitting it in two and
+    /// annotating the halves, splitting again up to `splits_left` times while
+    /// a half's output is still truncated. The extractions of the halves are
+    /// concatenated in document order.
+    ///
+    /// Where splitting runs out, the items `salvaged` from the truncated
+    /// output are kept. A half that fails is recorded in
+    /// `diagnostics.split_failures` and the other halves are kept; the chunk
+    /// only fails when no part of it yields anything.
+    async fn annotate_split(
+        &self,
+        chunk: &DocumentChunk,
+        request: &SplitRequest<'_>,
+        splits_left: usize,
+        salvaged: Option<Vec<Extraction>>,
+        run: &AnnotationRun<'_>,
+        diagnostics: &mut ChunkDiagnostics,
+    ) -> Result<Vec<Extraction>, ChunkFailure> {
+        // Falls back to the salvaged items, noting what was lost.
+        let give_up = |failure: ChunkFailure, diagnostics: &mut ChunkDiagnostics| match &salvaged {
+            Some(salvaged) => {
+                diagnostics.split_failures.push(failure.to_string());
+                Ok(salvaged.clone())
+            }
+            None => Err(failure),
+        };
+        let halves = chunk.split();
+        if splits_left == 0 || halves.is_empty() {
+            let failure = ChunkFailure::new(
+                chunk.char_interval.clone(),
+                ChunkErrorKind::Truncated,
+                "Output still truncated and the chunk cannot be split further.",
+                None,
+            );
+            return give_up(failure, diagnostics);
+        }
+        if run.debug {
+            println!(
+                "Splitting chunk {:?} into {} parts after truncated output.",
+                chunk.char_interval,
+                halves.len()
             );
-            annotated_documents.push(annotated_doc);
         }
-        println!("Document annotation completed.");
-        Ok(annotated_documents)
+        let batch: Vec<(&DocumentChunk, &[Extraction])> = halves.iter().map(|half| (half, request.prior)).collect();
+        let prompts = self.render_prompts(&batch, request.additional_context, request.pass_index, 0);
+        let mut outputs = match self.infer_limited(&prompts, request.inference_kwargs.clone()).await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                let failure =
+                    ChunkFailure::new(chunk.char_interval.clone(), ChunkErrorKind::Inference, e.message, None);
+                return give_up(failure, diagnostics);
+            }
+        };
+        outputs.resize(halves.len(), Vec::new());
+
+        let mut extractions = Vec::new();
+        let mut failures = Vec::new();
+        for (half, scored_outputs) in halves.iter().zip(outputs) {
+            let outcome = match self.resolve_chunk(half, Ok(scored_outputs), run, diagnostics) {
+                Err(mut failure) if failure.error_kind == ChunkErrorKind::Truncated => {
+                    let salvaged = failure.salvaged.take();
+                    Box::pin(self.annotate_split(half, request, splits_left - 1, salvaged, run, diagnostics)).await
+                }
+                outcome => outcome,
+            };
+            match outcome {
+                Ok(half_extractions) => extractions.extend(half_extractions),
+                Err(failure) => failures.push(failure),
+            }
+        }
+        if failures.len() == halves.len() && salvaged.is_none() {
+            return Err(failures.swap_remove(0));
+        }
+        diagnostics
+            .split_failures
+            .extend(failures.iter().map(ToString::to_string));
+        if failures.len() == halves.len() {
+            return Ok(salvaged.unwrap_or_default());
+        }
+        Ok(extractions)
     }
 
-    /// Sequential extraction passes logic for improved recall.
-    async fn annotate_documents_sequential_passes(
+    /// Renders the prompt of every chunk in a batch for one sample of a pass,
+    /// listing the extractions earlier passes found in the chunk.
+    fn render_prompts(
         &self,
-        documents: Vec<Document>,
-        resolver: &dyn AbstractResolver,
-        max_char_buffer: usize,
-        batch_length: usize,
-        debug: bool,
-        extraction_passes: usize,
-        extra_args: Option<HashMap<String, String>>,
-    ) -> Result<Vec<AnnotatedDocument>, InferenceOutputError> {
-        println!(
-            "Starting sequential extraction passes for improved recall with {} passes.",
-            extraction_passes
-        );
-        let document_list: Vec<Document> = documents;
-        let mut document_extractions_by_pass: HashMap<String, Vec<Vec<Extraction>>> = HashMap::new();
-        let mut document_texts: HashMap<String, String> = HashMap::new();
-
-        for pass_num in 0..extraction_passes {
-            println!("Starting extraction pass {} of {}", pass_num + 1, extraction_passes);
-            let annotated_docs = self
-                .annotate_documents_single_pass(
-                    document_list.clone(),
-                    resolver,
-                    max_char_buffer,
-                    batch_length,
-                    debug && pass_num == 0,
-                    extra_args.clone(),
-                )
-                .await?;
-            for mut annotated_doc in annotated_docs {
-                let doc_id = annotated_doc.document_id().clone();
-                document_extractions_by_pass
-                    .entry(doc_id.clone())
-                    .or_default()
-                    .push(annotated_doc.extractions.clone().unwrap_or_default());
-                document_texts
-                    .entry(doc_id.clone())
-                    .or_insert(annotated_doc.text.clone().unwrap_or_default());
-            }
-        }
-
-        let mut results = Vec::new();
-        for (doc_id, all_pass_extractions) in document_extractions_by_pass.iter() {
-            let merged_extractions = merge_non_overlapping_extractions(all_pass_extractions);
-            if debug {
-                let total_extractions: usize = all_pass_extractions.iter().map(|extractions| extractions.len()).sum();
-                println!(
-                    "Document {}: Merged {} extractions from {} passes into {} non-overlapping extractions.",
-                    doc_id,
-                    total_extractions,
-                    extraction_passes,
-                    merged_extractions.len(),
+        batch: &[(&DocumentChunk, &[Extraction])],
+        additional_context: Option<&str>,
+        pass_index: usize,
+        sample_index: usize,
+    ) -> Vec<String> {
+        let prompt_generator = self.prompt_generator_for(pass_index, sample_index);
+        batch
+            .iter()
+            .map(|(chunk, prior)| {
+                let prior: Vec<PromptExtraction> = prior
+                    .iter()
+                    .map(|e| PromptExtraction {
+                        extraction_class: e.extraction_class.clone(),
+                        extraction_text: e.extraction_text.clone(),
+                        attributes: None,
+                    })
+                    .collect();
+                prompt_generator.render_with_prior_extractions(&chunk.text, additional_context, &prior)
+            })
+            .collect()
+    }
+
+    /// Draws the self-consistency samples after the first one for every chunk
+    /// of a batch, returning the extractions of each sample per chunk.
+    ///
+    /// Samples are independent requests sent concurrently; a failed request or
+    /// unparsable output counts as a sample without extractions.
+    async fn sample_again(
+        &self,
+        batch: &[(&DocumentChunk, &[Extraction])],
+        additional_context: Option<&str>,
+        pass_index: usize,
+        run: &AnnotationRun<'_>,
+    ) -> Vec<Vec<Vec<Extraction>>> {
+        let Some(self_consistency) = &self.self_consistency else {
+            return Vec::new();
+        };
+        let inference_kwargs = self.inference_kwargs_for_pass(pass_index);
+        let samples = future::join_all((1..self_consistency.samples).map(|sample_index| {
+            let prompts = self.render_prompts(batch, additional_context, pass_index, sample_index);
+            let inference_kwargs = inference_kwargs.clone();
+            async move { self.infer_limited(&prompts, inference_kwargs).await }
+        }))
+        .await;
+
+        let mut chunk_samples = vec![Vec::new(); batch.len()];
+        for outputs in samples {
+            let mut outputs = outputs.unwrap_or_else(|e| {
+                self.notify(|o| o.on_error(&e));
+                Vec::new()
+            });
+            outputs.resize(batch.len(), Vec::new());
+            for ((chunk, _), (samples, scored_outputs)) in batch.iter().zip(chunk_samples.iter_mut().zip(outputs)) {
+                let mut diagnostics = ChunkDiagnostics::default();
+                samples.push(
+                    self.resolve_chunk(chunk, Ok(scored_outputs), run, &mut diagnostics)
+                        .unwrap_or_default(),
                 );
             }
-            results.push(AnnotatedDocument::new(
-                Some(doc_id.clone()),
-                Some(merged_extractions),
-                Some(document_texts.get(doc_id).cloned().unwrap_or_default()),
+        }
+        chunk_samples
+    }
+
+    /// Returns the prompt generator for a sample of a pass, with the few-shot
+    /// examples rotated by the pass index when example rotation is enabled,
+    /// and further by the sample index when example shuffling is.
+    fn prompt_generator_for(&self, pass_index: usize, sample_index: usize) -> Cow<'_, QAPromptGenerator> {
+        let num_examples = self.prompt_generator.template.examples.len();
+        let mut rotation = if self.pass_prompting.rotate_examples {
+            pass_index
+        } else {
+            0
+        };
+        if self.self_consistency.as_ref().is_some_and(|sc| sc.shuffle_examples) {
+            rotation += sample_index;
+        }
+        if num_examples < 2 || rotation.is_multiple_of(num_examples) {
+            return Cow::Borrowed(&self.prompt_generator);
+        }
+        let mut prompt_generator = self.prompt_generator.clone();
+        prompt_generator.template.examples.rotate_left(rotation % num_examples);
+        Cow::Owned(prompt_generator)
+    }
+
+    /// Returns the inference arguments for a pass, overriding the temperature
+    /// with the self-consistency temperature or the one configured for the
+    /// pass.
+    fn inference_kwargs_for_pass(&self, pass_index: usize) -> Option<HashMap<String, serde_json::Value>> {
+        let temperature = self
+            .self_consistency
+            .as_ref()
+            .and_then(|sc| sc.temperature)
+            .or_else(|| self.pass_prompting.pass_temperatures.get(pass_index).copied())?;
+        Some(HashMap::from([("temperature".to_string(), temperature.into())]))
+    }
+
+    /// Runs inference once a concurrency slot is free.
+    async fn infer_limited(
+        &self,
+        prompts: &[String],
+        kwargs: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+        let _slot = self
+            .inference_slots
+            .acquire()
+            .await
+            .map_err(|e| InferenceOutputError::new(e.to_string()))?;
+        self.language_model.infer(prompts, kwargs).await
+    }
+
+    /// Runs inference for a single chunk prompt.
+    async fn infer_chunk(
+        &self,
+        prompt: &str,
+        kwargs: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<Vec<ScoredOutput>, InferenceOutputError> {
+        let mut outputs = self.infer_limited(&[prompt.to_string()], kwargs).await?;
+        Ok(outputs.pop().unwrap_or_default())
+    }
+
+    /// Resolves and aligns the top output of one chunk, describing why the
+    /// chunk failed if it produced no usable output.
+    ///
+    /// With an ensemble agreement, every output is resolved as one member's
+    /// answer and the extractions enough members agree on are kept; the chunk
+    /// only fails when no member's output is usable.
+    fn resolve_chunk(
+        &self,
+        chunk: &DocumentChunk,
+        scored_outputs: Result<Vec<ScoredOutput>, InferenceOutputError>,
+        run: &AnnotationRun<'_>,
+        diagnostics: &mut ChunkDiagnostics,
+    ) -> Result<Vec<Extraction>, ChunkFailure> {
+        let scored_outputs = scored_outputs
+            .map_err(|e| ChunkFailure::new(chunk.char_interval.clone(), ChunkErrorKind::Inference, e.message, None))?;
+        let Some(agreement) = self.ensemble_agreement else {
+            return self.resolve_output(chunk, scored_outputs.into_iter().next(), run, diagnostics);
+        };
+
+        let members = scored_outputs.len();
+        let mut samples = Vec::with_capacity(members);
+        let mut failures = Vec::new();
+        for scored_output in scored_outputs {
+            match self.resolve_output(chunk, Some(scored_output), run, diagnostics) {
+                Ok(extractions) => samples.push(extractions),
+                Err(failure) => {
+                    failures.push(failure);
+                    samples.push(Vec::new());
+                }
+            }
+        }
+        if failures.len() == members {
+            return Err(failures.into_iter().next().unwrap_or_else(|| {
+                ChunkFailure::new(
+                    chunk.char_interval.clone(),
+                    ChunkErrorKind::EmptyOutput,
+                    "No scored outputs from language model.",
+                    None,
+                )
+            }));
+        }
+        Ok(vote_overlapping(&samples, agreement.min_votes(members)))
+    }
+
+    /// Resolves and aligns one output of the model for a chunk, recording the
+    /// model that produced it on every extraction, and in `diagnostics` any
+    /// repairs the resolver applied and, when kept, the model's reasoning.
+    fn resolve_output(
+        &self,
+        chunk: &DocumentChunk,
+        scored_output: Option<ScoredOutput>,
+        run: &AnnotationRun<'_>,
+        diagnostics: &mut ChunkDiagnostics,
+    ) -> Result<Vec<Extraction>, ChunkFailure> {
+        let failure = |error_kind, message: String, raw_output| {
+            ChunkFailure::new(chunk.char_interval.clone(), error_kind, message, raw_output)
+        };
+        let model_id = scored_output.as_ref().and_then(|scored| scored.model_id.clone());
+        let truncated = scored_output.as_ref().is_some_and(|scored| scored.is_truncated());
+        let mut reasoning = scored_output.as_ref().and_then(|scored| scored.reasoning.clone());
+        let Some(top_inference_result) = scored_output.and_then(|top| top.output) else {
+            return Err(failure(
+                ChunkErrorKind::EmptyOutput,
+                "No scored outputs from language model.".to_string(),
+                None,
             ));
+        };
+        let (think, top_inference_result) = split_reasoning(&top_inference_result);
+        if let Some(think) = think {
+            reasoning = Some(match reasoning {
+                Some(reasoning) => format!("{}\n\n{}", reasoning, think),
+                None => think,
+            });
         }
-        println!("Sequential extraction passes completed.");
-        Ok(results)
+        if self.keep_reasoning && reasoning.is_some() {
+            diagnostics.reasoning = reasoning;
+        }
+
+        // Output cut off at the token limit may still parse, minus whatever
+        // extractions did not fit, so it is never used as is.
+        if truncated {
+            return Err(failure(
+                ChunkErrorKind::Truncated,
+                "Output hit the model's output token limit.".to_string(),
+                Some(top_inference_result),
+            ));
+        }
+        let (resolved, repairs) = run
+            .resolver
+            .resolve_with_repairs(&top_inference_result, false)
+            .map_err(|e| {
+                let error_kind = if looks_truncated(&top_inference_result) {
+                    ChunkErrorKind::Truncated
+                } else {
+                    ChunkErrorKind::Parse
+                };
+                failure(error_kind, e.to_string(), Some(top_inference_result.clone()))
+            })?;
+        let cut_off = repairs.iter().any(ParseRepair::is_truncation);
+        if !cut_off || self.max_truncation_splits == 0 {
+            diagnostics
+                .parse_repairs
+                .extend(repairs.iter().map(ToString::to_string));
+        }
+        let aligned_extractions = run.resolver.align(
+            &resolved,
+            &chunk.text,
+            chunk.token_interval.start_index,
+            chunk.char_interval.start_pos,
+            self.enable_fuzzy_alignment,
+            self.fuzzy_alignment_threshold,
+            self.accept_match_lesser,
+        );
+        let extractions = aligned_extractions
+            .into_iter()
+            .map(|e| {
+                let mut extraction = to_data_extraction(e);
+                extraction.model_ids.extend(model_id.clone());
+                extraction
+            })
+            .collect();
+        // Items salvaged from truncated output ride along on the failure and
+        // are only used when splitting the chunk cannot recover the rest.
+        if cut_off && self.max_truncation_splits > 0 {
+            let mut failure = failure(
+                ChunkErrorKind::Truncated,
+                "Output was cut off before it was complete.".to_string(),
+                Some(top_inference_result),
+            );
+            failure.salvaged = Some(extractions);
+            return Err(failure);
+        }
+        Ok(extractions)
     }
 
     /// Annotates text with NLP extractions for text input.
+    #[allow(clippy::too_many_arguments)]
     pub async fn annotate_text(
         &self,
         text: &str,
@@ -434,13 +1619,13 @@ impl<L: BaseLanguageModel> Annotator<L> {
             "Expected 1 annotation but got {} annotations.",
             annotations.len()
         );
-        if debug && annotations[0].extractions.as_ref().map_or(false, |v| !v.is_empty()) {
+        if debug && annotations[0].extractions.as_ref().is_some_and(|v| !v.is_empty()) {
             let elapsed_time = start_time.map(|t| t.elapsed().as_secs_f64());
             let num_extractions = annotations[0].extractions.as_ref().map_or(0, |v| v.len());
             let unique_classes = annotations[0].extractions.as_ref().map_or(0, |v| {
                 v.iter().map(|e| &e.extraction_class).collect::<HashSet<_>>().len()
             });
-            let num_chunks = text.len() / max_char_buffer + if text.len() % max_char_buffer > 0 { 1 } else { 0 };
+            let num_chunks = text.len().div_ceil(max_char_buffer);
             progress::print_extraction_summary(
                 num_extractions,
                 unique_classes,
@@ -449,10 +1634,1031 @@ impl<L: BaseLanguageModel> Annotator<L> {
                 Some(num_chunks),
             );
         }
-        Ok(AnnotatedDocument::new(
-            Some(annotations[0].document_id()),
-            annotations[0].extractions.clone(),
-            annotations[0].text.clone(),
-        ))
+        Ok(annotations.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::JsonlCheckpointStore;
+    use crate::data::AlignmentStatus;
+    use crate::ensemble::EnsembleModel;
+    use crate::inference::ScoredOutput;
+    use crate::normalization::{CaseFold, Nfkc, NormalizerChain};
+    use crate::prefilter::KeywordFilter;
+    use crate::progress::{AnnotationEvent, ChannelObserver};
+    use crate::resolver::Resolver;
+    use crate::verification::Verifier;
+    use async_trait::async_trait;
+
+    type Respond = dyn Fn(&str) -> Result<String, InferenceOutputError> + Send + Sync;
+
+    /// Language model stub that answers every prompt with the output of `respond`.
+    /// A batch fails as a whole if `respond` fails for any of its prompts.
+    struct MockLanguageModel {
+        respond: Box<Respond>,
+    }
+
+    impl MockLanguageModel {
+        fn new(respond: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+            Self::fallible(move |prompt| Ok(respond(prompt)))
+        }
+
+        fn fallible(respond: impl Fn(&str) -> Result<String, InferenceOutputError> + Send + Sync + 'static) -> Self {
+            Self {
+                respond: Box::new(respond),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for MockLanguageModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: Option<HashMap<String, serde_json::Value>>,
+        ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+            batch_prompts
+                .iter()
+                .map(|prompt| Ok(vec![ScoredOutput::new(Some(1.0), Some((self.respond)(prompt)?))]))
+                .collect()
+        }
+    }
+
+    fn prompt_template() -> PromptTemplateStructured {
+        PromptTemplateStructured {
+            description: "Extract people.".to_string(),
+            examples: vec![],
+        }
+    }
+
+    fn yaml_resolver() -> Resolver {
+        Resolver::new(true, Some("_index".to_string()), Some("_attributes".to_string()), true)
+    }
+
+    #[tokio::test]
+    async fn test_observer_receives_annotation_events() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Alice\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_observer(ChannelObserver::new(sender));
+        let documents = vec![
+            Document::new("Alice went home.".to_string(), Some("doc_a".to_string()), None),
+            Document::new("Alice stayed.".to_string(), Some("doc_b".to_string()), None),
+        ];
+
+        let annotated = annotator
+            .annotate_documents(documents, &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(annotated.len(), 2);
+        drop(annotator);
+
+        let events: Vec<_> = receiver.iter().collect();
+        assert!(matches!(
+            events.first(),
+            Some(AnnotationEvent::RunStart { total_chars: Some(29) })
+        ));
+        assert!(matches!(events.last(), Some(AnnotationEvent::RunDone)));
+        let count = |f: fn(&AnnotationEvent) -> bool| events.iter().filter(|e| f(e)).count();
+        assert_eq!(count(|e| matches!(e, AnnotationEvent::BatchStart { .. })), 2);
+        assert_eq!(
+            count(|e| matches!(e, AnnotationEvent::ChunkDone { num_extractions: 1, .. })),
+            2
+        );
+        assert_eq!(count(|e| matches!(e, AnnotationEvent::DocumentDone { .. })), 2);
+        assert_eq!(count(|e| matches!(e, AnnotationEvent::PassDone { .. })), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_starts_on_first_poll() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Alice\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_observer(ChannelObserver::new(sender));
+        let resolver = yaml_resolver();
+        let documents = vec![Document::new("Alice went home.".to_string(), None, None)];
+
+        let mut annotated =
+            Box::pin(annotator.annotate_stream(stream::iter(documents), &resolver, 1000, 1, false, 1, None));
+        assert!(receiver.try_recv().is_err());
+
+        annotated.next().await.unwrap().unwrap();
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(AnnotationEvent::RunStart { total_chars: None })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_annotate_stream_is_lazy_and_ordered() {
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Alice\n```".to_string());
+        let annotator =
+            Annotator::new(model, prompt_template(), FormatType::Yaml, None, true).with_documents_in_flight(2);
+        let resolver = yaml_resolver();
+        let pulled = AtomicUsize::new(0);
+        let documents = (0..100).map(|i| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+            Document::new(format!("Alice number {}.", i), Some(format!("doc_{}", i)), None)
+        });
+
+        let mut annotated =
+            Box::pin(annotator.annotate_stream(stream::iter(documents), &resolver, 1000, 4, false, 1, None));
+        let mut first = annotated.next().await.unwrap().unwrap();
+        assert_eq!(first.document_id(), "doc_0");
+        assert!(pulled.load(Ordering::SeqCst) <= 3);
+
+        let mut rest: Vec<_> = annotated.try_collect().await.unwrap();
+        assert_eq!(rest.len(), 99);
+        assert_eq!(rest[98].document_id(), "doc_99");
+        assert_eq!(rest[0].extractions.as_ref().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_document_ids_are_rejected() {
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions: []\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true);
+        let documents = vec![
+            Document::new("One.".to_string(), Some("same".to_string()), None),
+            Document::new("Two.".to_string(), Some("same".to_string()), None),
+        ];
+        let result = annotator
+            .annotate_documents(documents, &yaml_resolver(), 1000, 1, false, 1, None)
+            .await;
+        assert!(result.unwrap_err().message.contains("already visited"));
+    }
+
+    /// Answers with a person extraction, except for prompts whose chunk
+    /// mentions "Boom" (HTTP failure) or "Garbage" (unparseable output).
+    fn flaky_model() -> MockLanguageModel {
+        MockLanguageModel::fallible(|prompt| {
+            let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+            if chunk.contains("Boom") {
+                Err(InferenceOutputError::new("HTTP 500"))
+            } else if chunk.contains("Garbage") {
+                Ok("```yaml\nextractions: [unclosed\n```".to_string())
+            } else {
+                Ok("```yaml\nextractions:\n  - person: Alice\n```".to_string())
+            }
+        })
+    }
+
+    fn flaky_document() -> Document {
+        Document::new(
+            "Alice ran home. Boom went the drum. Garbage was out. Alice slept.".to_string(),
+            Some("doc_flaky".to_string()),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_skip_chunk_records_failures() {
+        let annotator = Annotator::new(flaky_model(), prompt_template(), FormatType::Yaml, None, true)
+            .with_failure_policy(FailurePolicy::SkipChunk);
+
+        let annotated = annotator
+            .annotate_documents(vec![flaky_document()], &yaml_resolver(), 20, 4, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+
+        assert_eq!(document.extractions.as_ref().unwrap().len(), 2);
+        assert_eq!(document.chunk_failures.len(), 2);
+        let inference_failure = &document.chunk_failures[0];
+        assert_eq!(inference_failure.error_kind, ChunkErrorKind::Inference);
+        assert_eq!(inference_failure.char_interval.start_pos, Some(16));
+        assert_eq!(inference_failure.char_interval.end_pos, Some(35));
+        assert!(inference_failure.raw_output.is_none());
+        let parse_failure = &document.chunk_failures[1];
+        assert_eq!(parse_failure.error_kind, ChunkErrorKind::Parse);
+        assert!(parse_failure.raw_output.as_deref().unwrap().contains("[unclosed"));
+    }
+
+    #[tokio::test]
+    async fn test_annotate_text_keeps_chunk_failures() {
+        let annotator = Annotator::new(flaky_model(), prompt_template(), FormatType::Yaml, None, true)
+            .with_failure_policy(FailurePolicy::SkipChunk);
+
+        let document = annotator
+            .annotate_text(&flaky_document().text, &yaml_resolver(), 20, 4, None, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(document.extractions.as_ref().unwrap().len(), 2);
+        assert_eq!(document.chunk_failures.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_aborts_on_parse_error() {
+        let annotator = Annotator::new(flaky_model(), prompt_template(), FormatType::Yaml, None, true);
+        let document = Document::new("Garbage was out.".to_string(), None, None);
+
+        let result = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await;
+        assert!(result.unwrap_err().message.contains("parse failure"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_chunk_recovers_from_transient_failures() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let model_calls = Arc::clone(&calls);
+        let model = MockLanguageModel::fallible(move |_| match model_calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(InferenceOutputError::new("HTTP 503")),
+            1 => Ok(String::new()),
+            _ => Ok("```yaml\nextractions:\n  - person: Alice\n```".to_string()),
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_failure_policy(FailurePolicy::RetryChunk { max_retries: 2 });
+        let document = Document::new("Alice ran home.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(annotated[0].extractions.as_ref().unwrap().len(), 1);
+        assert!(annotated[0].chunk_failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_store_resumes_without_reannotating() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let annotator = |fail_on: &'static str| {
+            let model_calls = Arc::clone(&calls);
+            let model = MockLanguageModel::fallible(move |prompt| {
+                model_calls.fetch_add(1, Ordering::SeqCst);
+                if prompt.rsplit("Q: ").next().unwrap_or_default().contains(fail_on) {
+                    Err(InferenceOutputError::new("connection reset"))
+                } else {
+                    Ok("```yaml\nextractions:\n  - person: Alice\n```".to_string())
+                }
+            });
+            Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+                .with_checkpoint_store(JsonlCheckpointStore::open(&path).unwrap())
+        };
+        let document = || {
+            Document::new(
+                "Alice ran home. Alice sat down. Alice slept.".to_string(),
+                Some("doc_resume".to_string()),
+                None,
+            )
+        };
+
+        let interrupted = annotator("slept")
+            .annotate_documents(vec![document()], &yaml_resolver(), 16, 1, false, 1, None)
+            .await;
+        assert!(interrupted.is_err());
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 3);
+
+        let resumed = annotator("never")
+            .annotate_documents(vec![document()], &yaml_resolver(), 16, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let extractions = resumed[0].extractions.as_ref().unwrap();
+        assert_eq!(extractions.len(), 3);
+        let starts: Vec<_> = extractions
+            .iter()
+            .map(|e| e.char_interval.as_ref().unwrap().start_pos.unwrap())
+            .collect();
+        assert_eq!(starts, vec![0, 16, 32]);
+    }
+
+    /// Language model that records how many requests are in flight at once.
+    struct ConcurrencyProbe {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for ConcurrencyProbe {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: Option<HashMap<String, serde_json::Value>>,
+        ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(batch_prompts
+                .iter()
+                .map(|prompt| {
+                    let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+                    let word = chunk.split_whitespace().next().unwrap_or_default();
+                    let output = format!("```yaml\nextractions:\n  - person: {}\n```", word);
+                    vec![ScoredOutput::new(Some(1.0), Some(output))]
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_passes_share_global_concurrency_limit() {
+        let model = ConcurrencyProbe {
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        };
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_documents_in_flight(2)
+            .with_max_concurrency(3);
+        let documents = (0..3)
+            .map(|i| {
+                Document::new(
+                    "Alice ran. Bob ran. Carol ran. Dave ran. Erin ran.".to_string(),
+                    Some(format!("doc_{}", i)),
+                    None,
+                )
+            })
+            .collect();
+
+        let annotated = annotator
+            .annotate_documents(documents, &yaml_resolver(), 12, 1, false, 2, None)
+            .await
+            .unwrap();
+
+        assert_eq!(annotator.language_model.max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(annotated.len(), 3);
+        for (i, mut document) in annotated.into_iter().enumerate() {
+            assert_eq!(document.document_id(), format!("doc_{}", i));
+            let names: Vec<_> = document
+                .extractions
+                .unwrap()
+                .iter()
+                .map(|e| e.extraction_text.clone())
+                .collect();
+            assert_eq!(names, vec!["Alice", "Bob", "Carol", "Dave", "Erin"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_passes_of_documents_without_id_are_merged() {
+        let pass = AtomicUsize::new(0);
+        let model = MockLanguageModel::new(move |_| {
+            if pass.fetch_add(1, Ordering::SeqCst).is_multiple_of(2) {
+                "```yaml\nextractions:\n  - person: Alice\n```".to_string()
+            } else {
+                "```yaml\nextractions:\n  - person: Bob\n```".to_string()
+            }
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_merge_strategy(crate::merging::Union);
+        let documents = vec![
+            Document::new("Bob met Alice.".to_string(), None, None),
+            Document::new("Bob met Alice again.".to_string(), None, None),
+        ];
+
+        let annotated = annotator
+            .annotate_documents(documents, &yaml_resolver(), 1000, 1, false, 2, None)
+            .await
+            .unwrap();
+        assert_eq!(annotated.len(), 2);
+        assert_eq!(annotated[1].text.as_deref(), Some("Bob met Alice again."));
+        for document in &annotated {
+            let names: Vec<_> = document
+                .extractions
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|e| e.extraction_text.as_str())
+                .collect();
+            assert_eq!(names, vec!["Bob", "Alice"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_missed_passes_list_prior_extractions() {
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&prompts);
+        let model = MockLanguageModel::new(move |prompt| {
+            seen.lock().unwrap().push(prompt.to_string());
+            if prompt.contains("- person: Alice") {
+                "```yaml\nextractions:\n  - person: Bob\n```".to_string()
+            } else {
+                "```yaml\nextractions:\n  - person: Alice\n```".to_string()
+            }
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_concurrency(4)
+            .with_pass_prompting(PassPrompting {
+                find_missed: true,
+                ..Default::default()
+            });
+        let document = Document::new("Alice met Bob.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 2, None)
+            .await
+            .unwrap();
+
+        let names: Vec<_> = annotated[0]
+            .extractions
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| e.extraction_text.as_str())
+            .collect();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(!prompts[0].contains("Already extracted"));
+        assert!(prompts[1].contains("Already extracted"));
+    }
+
+    #[tokio::test]
+    async fn test_chunk_overlap_reports_boundary_entities_once() {
+        // Sees "Bob Stone" whole only when the chunk includes the sentence before.
+        let model = MockLanguageModel::new(|prompt| {
+            let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+            let mut yaml = String::from("```yaml\nextractions:\n");
+            if chunk.contains("Bob") && chunk.contains("Stone") {
+                yaml.push_str("  - person: Bob Stone\n");
+            } else if chunk.contains("Bob") {
+                yaml.push_str("  - person: Bob\n");
+            }
+            if chunk.contains("Carol") {
+                yaml.push_str("  - person: Carol\n");
+            }
+            yaml + "```"
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_chunk_overlap(ChunkOverlap::Characters(10));
+        let document = Document::new("I saw Bob\nStone today. Carol left.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 10, 1, false, 1, None)
+            .await
+            .unwrap();
+
+        let names: Vec<_> = annotated[0]
+            .extractions
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| e.extraction_text.as_str())
+            .collect();
+        assert_eq!(names, vec!["Bob Stone", "Carol"]);
+    }
+
+    #[tokio::test]
+    async fn test_progress_counts_overlapping_chunks_once() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions: []\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_chunk_overlap(ChunkOverlap::Characters(10))
+            .with_observer(ChannelObserver::new(sender));
+        let document = Document::new("I saw Bob\nStone today. Carol left.".to_string(), None, None);
+
+        annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 10, 1, false, 2, None)
+            .await
+            .unwrap();
+        drop(annotator);
+
+        let events: Vec<_> = receiver.iter().collect();
+        let Some(AnnotationEvent::RunStart {
+            total_chars: Some(total),
+        }) = events.first()
+        else {
+            panic!("run did not start with a known total");
+        };
+        let processed: usize = events
+            .iter()
+            .filter_map(|e| match e {
+                AnnotationEvent::CharsProcessed { chars } => Some(*chars),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(processed, *total);
+    }
+
+    #[tokio::test]
+    async fn test_find_missed_passes_report_each_pass_when_done() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Alice\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_pass_prompting(PassPrompting {
+                find_missed: true,
+                ..Default::default()
+            })
+            .with_observer(ChannelObserver::new(sender));
+        let document = Document::new("Alice met Bob.".to_string(), None, None);
+
+        annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 2, None)
+            .await
+            .unwrap();
+        drop(annotator);
+
+        let order: Vec<&str> = receiver
+            .iter()
+            .filter_map(|e| match e {
+                AnnotationEvent::BatchStart { .. } => Some("batch"),
+                AnnotationEvent::PassDone { pass_index: 0, .. } => Some("pass 0 done"),
+                AnnotationEvent::PassDone { pass_index: 1, .. } => Some("pass 1 done"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(order, vec!["batch", "pass 0 done", "batch", "pass 1 done"]);
+    }
+
+    #[tokio::test]
+    async fn test_self_consistency_keeps_agreed_extractions() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let model = MockLanguageModel::new(move |_| {
+            let names = match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => vec!["Alice", "Bob"],
+                1 => vec!["Bob Stone"],
+                _ => vec!["Alice"],
+            };
+            let extractions: String = names.iter().map(|name| format!("  - person: {}\n", name)).collect();
+            format!("```yaml\nextractions:\n{}```", extractions)
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_self_consistency(SelfConsistency::new(3, 2));
+        let document = Document::new("Alice met Bob Stone today.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let extractions = annotated[0].extractions.as_ref().unwrap();
+        let names: Vec<_> = extractions.iter().map(|e| e.extraction_text.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert!(
+            extractions
+                .iter()
+                .all(|e| (e.confidence.unwrap() - 2.0 / 3.0).abs() < 1e-9)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_self_consistency_samples_run_alongside_the_main_one() {
+        let model = ConcurrencyProbe {
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        };
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_concurrency(3)
+            .with_self_consistency(SelfConsistency::new(3, 2));
+        let document = Document::new("Alice ran.".to_string(), None, None);
+
+        annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+
+        // The main sample and both extra samples are in flight together.
+        assert_eq!(annotator.language_model.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_agreement_records_models() {
+        let member = |names: &'static [&'static str]| {
+            MockLanguageModel::new(move |_| {
+                let extractions: String = names.iter().map(|name| format!("  - person: {}\n", name)).collect();
+                format!("```yaml\nextractions:\n{}```", extractions)
+            })
+        };
+        let ensemble = EnsembleModel::new()
+            .with_member("a", member(&["Alice", "Bob"]))
+            .with_member("b", member(&["Bob Stone"]))
+            .with_member("c", MockLanguageModel::new(|_| "not yaml: [".to_string()));
+        let annotator = Annotator::new(ensemble, prompt_template(), FormatType::Yaml, None, true)
+            .with_ensemble_agreement(Agreement::Majority);
+        let document = Document::new("Alice met Bob Stone today.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+
+        let extractions = annotated[0].extractions.as_ref().unwrap();
+        let summary: Vec<_> = extractions
+            .iter()
+            .map(|e| (e.extraction_text.as_str(), e.model_ids.clone()))
+            .collect();
+        assert_eq!(summary, vec![("Bob", vec!["a".to_string(), "b".to_string()])]);
+        assert!((extractions[0].confidence.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_dry_run_renders_prompts_without_inference() {
+        let model = MockLanguageModel::new(|_| panic!("dry run must not call the model"));
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true);
+        let documents = vec![
+            Document::new(
+                "It rained today. Carol left early.".to_string(),
+                Some("doc_a".to_string()),
+                None,
+            ),
+            Document::new("Bob stayed.".to_string(), Some("doc_b".to_string()), None),
+        ];
+
+        let report = annotator.dry_run(documents, 20, 2).unwrap();
+
+        assert_eq!(report.num_documents, 2);
+        assert_eq!(report.num_chunks, 3);
+        assert_eq!(report.prompts.len(), 6);
+        let first = &report.prompts[0];
+        assert_eq!((first.document_id.as_str(), first.pass_index), ("doc_a", 0));
+        assert!(first.prompt.ends_with("Q: It rained today.\nA: "));
+        assert_eq!(first.estimated_prompt_tokens, estimate_tokens(&first.prompt));
+    }
+
+    #[test]
+    fn test_looks_truncated() {
+        assert!(looks_truncated("```yaml\nextractions:\n  - person: Al"));
+        assert!(looks_truncated(r#"{"extractions": [{"person": "Al"#));
+        assert!(looks_truncated(r#"[{"person": "a]b"#));
+        assert!(!looks_truncated("```yaml\nextractions: [unclosed\n```"));
+        assert!(!looks_truncated(r#"{"extractions": []}"#));
+        assert!(!looks_truncated("I cannot help with that."));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_chunk_is_split_and_retried() {
+        // Hits the output limit whenever a prompt holds more than one sentence.
+        struct TruncatingModel;
+
+        #[async_trait]
+        impl BaseLanguageModel for TruncatingModel {
+            async fn infer(
+                &self,
+                batch_prompts: &[String],
+                _kwargs: Option<HashMap<String, serde_json::Value>>,
+            ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+                Ok(batch_prompts
+                    .iter()
+                    .map(|prompt| {
+                        let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+                        let names: Vec<_> = ["Alice", "Bob", "Carol"]
+                            .into_iter()
+                            .filter(|name| chunk.contains(name))
+                            .collect();
+                        let extractions: String = names.iter().map(|name| format!("  - person: {}\n", name)).collect();
+                        let output =
+                            ScoredOutput::new(Some(1.0), Some(format!("```yaml\nextractions:\n{}```", extractions)));
+                        if names.len() > 1 {
+                            vec![output.with_finish_reason("length")]
+                        } else {
+                            vec![output]
+                        }
+                    })
+                    .collect())
+            }
+        }
+
+        let document = || Document::new("Alice ran. Bob sat. Carol left.".to_string(), None, None);
+        let annotator = Annotator::new(TruncatingModel, prompt_template(), FormatType::Yaml, None, true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let extractions = annotated[0].extractions.as_ref().unwrap();
+        let spans: Vec<_> = extractions
+            .iter()
+            .map(|e| (e.extraction_text.as_str(), e.char_interval.as_ref().unwrap().start_pos))
+            .collect();
+        assert_eq!(spans, vec![("Alice", Some(0)), ("Bob", Some(11)), ("Carol", Some(20))]);
+
+        let annotator = Annotator::new(TruncatingModel, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_truncation_splits(0)
+            .with_failure_policy(FailurePolicy::SkipChunk);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(annotated[0].chunk_failures[0].error_kind, ChunkErrorKind::Truncated);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_output_is_salvaged_when_splitting_runs_out() {
+        // Always cuts the output off in the middle of an item.
+        let model = MockLanguageModel::new(|prompt| {
+            let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+            let names: String = ["Alice", "Bob"]
+                .into_iter()
+                .filter(|name| chunk.contains(name))
+                .map(|name| format!("  - person: {}\n", name))
+                .collect();
+            format!("```yaml\nextractions:\n{}  - person: Ca", names)
+        });
+        let resolver = yaml_resolver().with_lenient_parsing(true);
+        let annotator =
+            Annotator::new(model, prompt_template(), FormatType::Yaml, None, true).with_max_truncation_splits(1);
+        let document = Document::new("Alice ran. Bob sat.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &resolver, 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+        let texts: Vec<_> = document
+            .extractions
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| e.extraction_text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["Alice", "Bob"]);
+        assert!(document.chunk_failures.is_empty());
+        assert_eq!(document.chunk_diagnostics[0].split_failures.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_half_keeps_its_sibling() {
+        // Hits the output limit on any prompt mentioning Bob, with nothing to salvage.
+        struct BobTruncatingModel;
+
+        #[async_trait]
+        impl BaseLanguageModel for BobTruncatingModel {
+            async fn infer(
+                &self,
+                batch_prompts: &[String],
+                _kwargs: Option<HashMap<String, serde_json::Value>>,
+            ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+                Ok(batch_prompts
+                    .iter()
+                    .map(|prompt| {
+                        let chunk = prompt.rsplit("Q: ").next().unwrap_or_default();
+                        let output = ScoredOutput::new(
+                            Some(1.0),
+                            Some("```yaml\nextractions:\n  - person: Alice\n```".to_string()),
+                        );
+                        if chunk.contains("Bob") {
+                            vec![output.with_finish_reason("length")]
+                        } else {
+                            vec![output]
+                        }
+                    })
+                    .collect())
+            }
+        }
+
+        let annotator = Annotator::new(BobTruncatingModel, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_truncation_splits(1);
+        let document = Document::new("Alice ran. Bob sat.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+        assert_eq!(document.extractions.as_ref().unwrap().len(), 1);
+        assert!(document.chunk_failures.is_empty());
+        let split_failures = &document.chunk_diagnostics[0].split_failures;
+        assert_eq!(split_failures.len(), 1);
+        assert!(split_failures[0].contains("cannot be split further"));
+    }
+
+    #[tokio::test]
+    async fn test_repair_loop_fixes_malformed_output() {
+        let model = MockLanguageModel::new(|prompt| {
+            if prompt.contains("could not be parsed") && prompt.contains("Alice") {
+                "```yaml\nextractions:\n  - person: Alice\n```".to_string()
+            } else {
+                "```yaml\nextractions: [unclosed\n```".to_string()
+            }
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_repair_attempts(2)
+            .with_failure_policy(FailurePolicy::SkipChunk);
+        let document = Document::new("Alice ran home. Bob slept.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 16, 1, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+
+        assert_eq!(document.extractions.as_ref().unwrap().len(), 1);
+        assert_eq!(document.chunk_failures.len(), 1);
+        assert_eq!(document.chunk_diagnostics.len(), 2);
+        let repaired = &document.chunk_diagnostics[0];
+        assert_eq!(repaired.repair_attempts.len(), 1);
+        assert_eq!(
+            repaired.repair_attempts[0].output,
+            "```yaml\nextractions: [unclosed\n```"
+        );
+        assert!(!repaired.repair_attempts[0].error.is_empty());
+        assert!(repaired.repaired_output.as_deref().unwrap().contains("person: Alice"));
+        let unrepaired = &document.chunk_diagnostics[1];
+        assert_eq!(unrepaired.repair_attempts.len(), 2);
+        assert!(unrepaired.repaired_output.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_is_not_parsed_and_kept_on_request() {
+        let model = MockLanguageModel::new(|_| {
+            "<think>\nMaybe:\n```yaml\nextractions:\n  - person: Eve\n```\n</think>\n```yaml\nextractions:\n  - person: Alice\n```"
+                .to_string()
+        });
+        let annotator =
+            Annotator::new(model, prompt_template(), FormatType::Yaml, None, true).with_reasoning_traces(true);
+        let document = Document::new("Alice met Bob Stone today.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+        let extractions = document.extractions.as_ref().unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert_eq!(extractions[0].extraction_text, "Alice");
+        let reasoning = document.chunk_diagnostics[0].reasoning.as_deref().unwrap();
+        assert!(reasoning.starts_with("Maybe:"));
+    }
+
+    #[tokio::test]
+    async fn test_lenient_parsing_records_repairs() {
+        let model = MockLanguageModel::new(|_| {
+            "Found one person.\n```yaml\nextractions:\n  - person: Alice\n  - person: Bo".to_string()
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_max_truncation_splits(0)
+            .with_failure_policy(FailurePolicy::SkipChunk);
+        let document = || Document::new("Alice met Bob Stone today.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(annotated[0].chunk_failures.len(), 1);
+
+        let resolver = yaml_resolver().with_lenient_parsing(true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &resolver, 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let document = &annotated[0];
+        assert!(document.chunk_failures.is_empty());
+        assert_eq!(document.extractions.as_ref().unwrap().len(), 1);
+        assert_eq!(
+            document.chunk_diagnostics[0].parse_repairs,
+            vec!["read unterminated code fence", "dropped incomplete item"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relevance_filter_skips_chunks() {
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&prompts);
+        let model = MockLanguageModel::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            "```yaml\nextractions:\n  - person: Carol\n```".to_string()
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_relevance_filter(KeywordFilter::new(["carol"]));
+        let document = Document::new("It rained today. Carol left early.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 20, 1, false, 2, None)
+            .await
+            .unwrap();
+
+        assert_eq!(prompts.load(Ordering::SeqCst), 2);
+        assert_eq!(annotated[0].skipped_chunks, vec![CharInterval::new(Some(0), Some(16))]);
+        assert_eq!(annotated[0].extractions.as_ref().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verifier_runs_after_resolution() {
+        let model =
+            MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Carol\n  - person: Bob\n```".to_string());
+        let verifier_model = MockLanguageModel::new(|prompt| {
+            assert!(prompt.contains("sentence: Carol left early."));
+            r#"[{"id": 1, "supported": true, "confidence": 0.9}, {"id": 2, "supported": false, "rationale": "Absent."}]"#
+                .to_string()
+        });
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true)
+            .with_verifier(Verifier::new(verifier_model).with_threshold(0.5));
+        let document = Document::new("Carol left early. Bob stayed.".to_string(), None, None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+
+        let extractions = annotated[0].extractions.as_ref().unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert_eq!(extractions[0].extraction_text, "Carol");
+        assert_eq!(extractions[0].confidence, Some(0.9));
+    }
+
+    #[tokio::test]
+    async fn test_failed_verification_follows_the_failure_policy() {
+        let model = || MockLanguageModel::new(|_| "```yaml\nextractions:\n  - person: Carol\n```".to_string());
+        let verifier = || Verifier::new(MockLanguageModel::new(|_| "I cannot tell.".to_string()));
+        let document = || Document::new("Carol left early.".to_string(), None, None);
+
+        let annotator =
+            Annotator::new(model(), prompt_template(), FormatType::Yaml, None, true).with_verifier(verifier());
+        let result = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await;
+        assert!(result.is_err());
+
+        let annotator = Annotator::new(model(), prompt_template(), FormatType::Yaml, None, true)
+            .with_verifier(verifier())
+            .with_failure_policy(FailurePolicy::SkipChunk);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let extractions = annotated[0].extractions.as_ref().unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert_eq!(extractions[0].confidence, None);
+        assert!(annotated[0].verification_error.is_some());
+        assert!(annotated[0].chunk_failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_alignment_settings_reach_the_resolver() {
+        let model =
+            MockLanguageModel::new(|_| "```yaml\nextractions:\n  - place: the harbour of Dover\n```".to_string());
+        let document = || {
+            Document::new(
+                "Alice met Bob at the harbour today.".to_string(),
+                Some("doc".to_string()),
+                None,
+            )
+        };
+
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(annotated[0].extractions.as_ref().unwrap()[0].alignment_status, None);
+
+        let annotator = annotator.with_match_lesser(true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let extraction = &annotated[0].extractions.as_ref().unwrap()[0];
+        assert_eq!(extraction.alignment_status, Some(AlignmentStatus::MatchLesser));
+        assert_eq!(extraction.char_interval, Some(CharInterval::new(Some(17), Some(28))));
+    }
+
+    #[tokio::test]
+    async fn test_fuzzy_alignment_finds_corrected_typos() {
+        let model = || MockLanguageModel::new(|_| "```yaml\nextractions:\n  - event: received\n```".to_string());
+        let document = || {
+            Document::new(
+                "The patient recieved aspirin.".to_string(),
+                Some("doc".to_string()),
+                None,
+            )
+        };
+
+        let annotator = Annotator::new(model(), prompt_template(), FormatType::Yaml, None, true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert!(annotated[0].extractions.as_ref().unwrap()[0].char_interval.is_none());
+
+        let annotator =
+            Annotator::new(model(), prompt_template(), FormatType::Yaml, None, true).with_fuzzy_alignment(true);
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let extraction = &annotated[0].extractions.as_ref().unwrap()[0];
+        assert_eq!(extraction.alignment_status, Some(AlignmentStatus::MatchFuzzy));
+        assert_eq!(extraction.char_interval, Some(CharInterval::new(Some(12), Some(20))));
+        assert_eq!(extraction.edit_distance, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_token_normalizer_applies_to_annotation() {
+        let model = MockLanguageModel::new(|_| "```yaml\nextractions:\n  - product: ABC\n```".to_string());
+        let annotator = Annotator::new(model, prompt_template(), FormatType::Yaml, None, true);
+        let document = || Document::new("Order ＡＢＣ widgets.".to_string(), Some("doc".to_string()), None);
+
+        let annotated = annotator
+            .annotate_documents(vec![document()], &yaml_resolver(), 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        assert!(annotated[0].extractions.as_ref().unwrap()[0].char_interval.is_none());
+
+        let resolver = yaml_resolver().with_token_normalizer(NormalizerChain::new().with(Nfkc).with(CaseFold));
+        let annotated = annotator
+            .annotate_documents(vec![document()], &resolver, 1000, 1, false, 1, None)
+            .await
+            .unwrap();
+        let extraction = &annotated[0].extractions.as_ref().unwrap()[0];
+        assert_eq!(extraction.alignment_status, Some(AlignmentStatus::MatchExact));
+        assert_eq!(extraction.char_interval, Some(CharInterval::new(Some(6), Some(9))));
     }
 }
@@ -0,0 +1,254 @@
+/*!
+Checkpointing of completed chunk results so long runs can resume.
+
+Every chunk that is annotated successfully is saved under its document id,
+character interval and extraction pass. When a run is restarted with the same
+store, chunks already present are not sent to the language model again and
+their saved extractions are reused to reassemble the annotated documents.
+
+Resuming relies on stable document ids, so give every `Document` an explicit
+id instead of relying on the generated default.
+
+Usage example:
+    let store = JsonlCheckpointStore::open("run.checkpoint.jsonl")?;
+    let annotator = Annotator::new(language_model, prompt_template, format_type, None, true)
+        .with_checkpoint_store(store);
+*/
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::data::{CharInterval, Extraction};
+use crate::data_lib::{dict_to_extraction, extraction_to_dict};
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("Checkpoint I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Checkpoint serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Identifies the result of one chunk in one extraction pass.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkKey {
+    pub document_id: String,
+    pub start_pos: usize,
+    pub end_pos: usize,
+    pub pass_index: usize,
+}
+
+impl ChunkKey {
+    pub fn new(document_id: &str, char_interval: &CharInterval, pass_index: usize) -> Self {
+        Self {
+            document_id: document_id.to_string(),
+            start_pos: char_interval.start_pos.unwrap_or(0),
+            end_pos: char_interval.end_pos.unwrap_or(0),
+            pass_index,
+        }
+    }
+}
+
+/// Persists the extractions of completed chunks.
+pub trait CheckpointStore: Send + Sync {
+    /// Returns the saved extractions of a chunk, if it was completed before.
+    fn load(&self, key: &ChunkKey) -> Option<Vec<Extraction>>;
+
+    /// Records a completed chunk. Must be durable once it returns.
+    fn save(&self, key: &ChunkKey, extractions: &[Extraction]) -> Result<(), CheckpointError>;
+}
+
+/// Checkpoint store backed by an append-only JSONL file, one line per chunk.
+///
+/// Every record is synced to disk before `save` returns. A line cut short by
+/// a crash, or otherwise malformed, is ignored when the file is reopened, so
+/// the affected chunk is simply annotated again.
+pub struct JsonlCheckpointStore {
+    path: PathBuf,
+    completed: Mutex<HashMap<ChunkKey, Vec<Extraction>>>,
+    writer: Mutex<File>,
+}
+
+impl JsonlCheckpointStore {
+    /// Opens the checkpoint file at `path`, loading any chunks recorded by a
+    /// previous run, or creates it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+
+        let mut completed = HashMap::new();
+        for line in BufReader::new(&file).lines() {
+            if let Some((key, extractions)) = parse_line(&line?) {
+                completed.insert(key, extractions);
+            }
+        }
+
+        // Terminate a truncated last line so the next record starts cleanly.
+        let len = file.metadata()?.len();
+        if len > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::Start(len - 1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        Ok(Self {
+            path,
+            completed: Mutex::new(completed),
+            writer: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of completed chunks recorded in the store.
+    pub fn len(&self) -> usize {
+        self.completed.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CheckpointStore for JsonlCheckpointStore {
+    fn load(&self, key: &ChunkKey) -> Option<Vec<Extraction>> {
+        self.completed.lock().unwrap().get(key).cloned()
+    }
+
+    fn save(&self, key: &ChunkKey, extractions: &[Extraction]) -> Result<(), CheckpointError> {
+        let mut line = serde_json::to_string(&to_line(key, extractions))?;
+        line.push('\n');
+        {
+            let mut writer = self.writer.lock().unwrap();
+            writer.write_all(line.as_bytes())?;
+            writer.flush()?;
+            writer.sync_data()?;
+        }
+        self.completed.lock().unwrap().insert(key.clone(), extractions.to_vec());
+        Ok(())
+    }
+}
+
+fn to_line(key: &ChunkKey, extractions: &[Extraction]) -> Value {
+    let mut map = Map::new();
+    map.insert("document_id".to_string(), Value::String(key.document_id.clone()));
+    map.insert("start_pos".to_string(), Value::Number(key.start_pos.into()));
+    map.insert("end_pos".to_string(), Value::Number(key.end_pos.into()));
+    map.insert("pass_index".to_string(), Value::Number(key.pass_index.into()));
+    map.insert(
+        "extractions".to_string(),
+        Value::Array(extractions.iter().map(extraction_to_dict).collect()),
+    );
+    Value::Object(map)
+}
+
+fn parse_line(line: &str) -> Option<(ChunkKey, Vec<Extraction>)> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    let usize_field = |name: &str| value.get(name).and_then(|v| v.as_u64()).map(|x| x as usize);
+    let key = ChunkKey {
+        document_id: value.get("document_id")?.as_str()?.to_string(),
+        start_pos: usize_field("start_pos")?,
+        end_pos: usize_field("end_pos")?,
+        pass_index: usize_field("pass_index")?,
+    };
+    // One malformed extraction invalidates the line rather than dropping it.
+    let extractions = value
+        .get("extractions")?
+        .as_array()?
+        .iter()
+        .map(dict_to_extraction)
+        .collect::<Option<Vec<_>>>()?;
+    Some((key, extractions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn key(pass_index: usize) -> ChunkKey {
+        ChunkKey::new("doc_1", &CharInterval::new(Some(0), Some(12)), pass_index)
+    }
+
+    #[test]
+    fn test_jsonl_store_reloads_saved_chunks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.jsonl");
+        let extraction = Extraction::new(
+            "person".to_string(),
+            "Alice".to_string(),
+            None,
+            Some(CharInterval::new(Some(0), Some(5))),
+            None,
+            Some(1),
+            Some(0),
+            None,
+            None,
+        );
+
+        let store = JsonlCheckpointStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        store.save(&key(0), std::slice::from_ref(&extraction)).unwrap();
+        store.save(&key(1), &[]).unwrap();
+        drop(store);
+
+        let reopened = JsonlCheckpointStore::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        let loaded = reopened.load(&key(0)).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].extraction_text, "Alice");
+        assert_eq!(loaded[0].char_interval.as_ref().unwrap().end_pos, Some(5));
+        assert!(reopened.load(&key(1)).unwrap().is_empty());
+        assert!(reopened.load(&key(2)).is_none());
+    }
+
+    #[test]
+    fn test_jsonl_store_ignores_truncated_line() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.jsonl");
+        let store = JsonlCheckpointStore::open(&path).unwrap();
+        store.save(&key(0), &[]).unwrap();
+        drop(store);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"document_id\": \"doc_1\", \"sta").unwrap();
+        drop(file);
+
+        let store = JsonlCheckpointStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        store.save(&key(1), &[]).unwrap();
+        drop(store);
+
+        let reopened = JsonlCheckpointStore::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+    }
+
+    #[test]
+    fn test_jsonl_store_ignores_malformed_extraction() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.jsonl");
+        std::fs::write(
+            &path,
+            concat!(
+                r#"{"document_id": "doc_1", "start_pos": 0, "end_pos": 12, "pass_index": 0, "extractions": "#,
+                r#"[{"extraction_class": "person", "extraction_text": "Alice", "token_interval": {"start": 0}}]}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let store = JsonlCheckpointStore::open(&path).unwrap();
+        assert!(store.load(&key(0)).is_none());
+    }
+}
@@ -7,6 +7,7 @@
 
 use std::fmt;
 use std::iter::Peekable;
+use std::sync::Arc;
 
 use crate::data::{CharInterval, Document};
 use crate::tokenizer::{self, TokenInterval, TokenizedText};
@@ -24,19 +25,25 @@ impl fmt::Display for TokenUtilError {
 impl std::error::Error for TokenUtilError {}
 
 /// Stores a text chunk with attributes to the source document.
+///
+/// The source document is shared between all chunks cut from it.
 #[derive(Debug, Clone)]
 pub struct TextChunk {
     pub token_interval: TokenInterval,
-    pub document: Option<Document>,
+    /// Leading tokens repeated from the previous chunk for context, when the
+    /// iterator was configured with a [`ChunkOverlap`].
+    pub overlap: Option<TokenInterval>,
+    pub document: Option<Arc<Document>>,
     chunk_text: Option<String>,
     sanitized_chunk_text: Option<String>,
     char_interval: Option<CharInterval>,
 }
 
 impl TextChunk {
-    pub fn new(token_interval: TokenInterval, document: Option<Document>) -> Self {
+    pub fn new(token_interval: TokenInterval, document: Option<Arc<Document>>) -> Self {
         Self {
             token_interval,
+            overlap: None,
             document,
             chunk_text: None,
             sanitized_chunk_text: None,
@@ -47,7 +54,7 @@ impl TextChunk {
     /// Gets the document ID from the source document.
     pub fn document_id(&self) -> Option<String> {
         self.document.as_ref().map(|doc| {
-            let mut_doc = &mut doc.clone();
+            let mut_doc = &mut Document::clone(doc);
             mut_doc.document_id()
         })
     }
@@ -55,7 +62,7 @@ impl TextChunk {
     /// Gets the tokenized text from the source document.
     pub fn document_text(&self) -> Option<TokenizedText> {
         self.document.as_ref().map(|doc| {
-            let mut_doc = &mut doc.clone();
+            let mut_doc = &mut Document::clone(doc);
             mut_doc.tokenized_text().clone()
         })
     }
@@ -190,6 +197,7 @@ pub fn get_char_interval(
 }
 
 /// Converts all whitespace characters in input text to a single space.
+#[allow(clippy::trim_split_whitespace)]
 pub fn sanitize(text: &str) -> Result<String, TokenUtilError> {
     let sanitized_text = text.trim().split_whitespace().collect::<Vec<_>>().join(" ");
     if sanitized_text.is_empty() {
@@ -253,13 +261,29 @@ impl<'a> Iterator for SentenceIterator<'a> {
     }
 }
 
+/// How much of the preceding text each chunk repeats for context.
+///
+/// The overlap is added in front of a chunk, so chunks can exceed the
+/// `max_char_buffer` by the size of the overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkOverlap {
+    #[default]
+    None,
+    /// Repeat up to this many preceding sentences.
+    Sentences(usize),
+    /// Repeat the preceding tokens that lie within this many characters.
+    Characters(usize),
+}
+
 /// Iterate through chunks of a tokenized text.
 pub struct ChunkIterator<'a> {
     tokenized_text: &'a TokenizedText,
     max_char_buffer: usize,
     sentence_iter: Peekable<SentenceIterator<'a>>,
-    document: Document,
+    document: Arc<Document>,
     broken_sentence: bool,
+    overlap: ChunkOverlap,
+    sentence_starts: Vec<usize>,
 }
 
 impl<'a> ChunkIterator<'a> {
@@ -269,11 +293,71 @@ impl<'a> ChunkIterator<'a> {
             tokenized_text: text,
             max_char_buffer,
             sentence_iter: SentenceIterator::new(text, 0).unwrap().peekable(),
-            document: doc,
+            document: Arc::new(doc),
             broken_sentence: false,
+            overlap: ChunkOverlap::None,
+            sentence_starts: Vec::new(),
+        }
+    }
+
+    /// Makes every chunk after the first repeat the end of the text before it.
+    pub fn with_overlap(mut self, overlap: ChunkOverlap) -> Self {
+        if let ChunkOverlap::Sentences(_) = overlap {
+            self.sentence_starts = SentenceIterator::new(self.tokenized_text, 0)
+                .unwrap()
+                .map(|sentence| sentence.start_index)
+                .collect();
+        }
+        self.overlap = overlap;
+        self
+    }
+
+    /// Returns the first token of the overlap in front of a chunk starting at
+    /// `start_index`.
+    fn overlap_start(&self, start_index: usize) -> usize {
+        match self.overlap {
+            ChunkOverlap::None => start_index,
+            ChunkOverlap::Sentences(sentences) => {
+                let preceding = self.sentence_starts.partition_point(|&start| start < start_index);
+                self.sentence_starts
+                    .get(preceding.saturating_sub(sentences))
+                    .copied()
+                    .filter(|_| sentences > 0)
+                    .map_or(start_index, |start| start.min(start_index))
+            }
+            ChunkOverlap::Characters(chars) => {
+                let tokens = &self.tokenized_text.tokens;
+                let Some(chunk_start) = tokens.get(start_index).map(|token| token.char_interval.start_pos) else {
+                    return start_index;
+                };
+                let mut overlap_start = start_index;
+                while overlap_start > 0 && chunk_start - tokens[overlap_start - 1].char_interval.start_pos <= chars {
+                    overlap_start -= 1;
+                }
+                overlap_start
+            }
         }
     }
 
+    /// Wraps a chunk's token interval, prepending the configured overlap.
+    fn make_chunk(&self, token_interval: TokenInterval) -> TextChunk {
+        let overlap_start = self.overlap_start(token_interval.start_index);
+        let mut chunk = TextChunk::new(
+            TokenInterval {
+                start_index: overlap_start,
+                end_index: token_interval.end_index,
+            },
+            Some(self.document.clone()),
+        );
+        if overlap_start < token_interval.start_index {
+            chunk.overlap = Some(TokenInterval {
+                start_index: overlap_start,
+                end_index: token_interval.start_index,
+            });
+        }
+        chunk
+    }
+
     fn tokens_exceed_buffer(&self, token_interval: &TokenInterval) -> bool {
         match get_char_interval(self.tokenized_text, token_interval) {
             Ok(char_interval) => {
@@ -300,7 +384,7 @@ impl<'a> Iterator for ChunkIterator<'a> {
                 .unwrap()
                 .peekable();
             self.broken_sentence = curr_chunk.end_index < sentence.end_index;
-            return Some(TextChunk::new(curr_chunk, Some(self.document.clone())));
+            return Some(self.make_chunk(curr_chunk));
         }
 
         let mut start_of_new_line = None;
@@ -313,19 +397,19 @@ impl<'a> Iterator for ChunkIterator<'a> {
                 end_index: token_index + 1,
             };
             if self.tokens_exceed_buffer(&test_chunk) {
-                if let Some(newline_idx) = start_of_new_line {
-                    if newline_idx > 0 {
-                        curr_chunk = TokenInterval {
-                            start_index: curr_chunk.start_index,
-                            end_index: newline_idx,
-                        };
-                    }
+                if let Some(newline_idx) = start_of_new_line
+                    && newline_idx > curr_chunk.start_index
+                {
+                    curr_chunk = TokenInterval {
+                        start_index: curr_chunk.start_index,
+                        end_index: newline_idx,
+                    };
                 }
                 self.sentence_iter = SentenceIterator::new(self.tokenized_text, curr_chunk.end_index)
                     .unwrap()
                     .peekable();
                 self.broken_sentence = true;
-                return Some(TextChunk::new(curr_chunk, Some(self.document.clone())));
+                return Some(self.make_chunk(curr_chunk));
             } else {
                 curr_chunk = test_chunk;
             }
@@ -343,7 +427,7 @@ impl<'a> Iterator for ChunkIterator<'a> {
                     self.sentence_iter = SentenceIterator::new(self.tokenized_text, curr_chunk.end_index)
                         .unwrap()
                         .peekable();
-                    return Some(TextChunk::new(curr_chunk, Some(self.document.clone())));
+                    return Some(self.make_chunk(curr_chunk));
                 } else {
                     curr_chunk = test_chunk;
                     self.sentence_iter.next();
@@ -351,7 +435,7 @@ impl<'a> Iterator for ChunkIterator<'a> {
             }
         }
 
-        Some(TextChunk::new(curr_chunk, Some(self.document.clone())))
+        Some(self.make_chunk(curr_chunk))
     }
 }
 
@@ -434,4 +518,46 @@ mod tests {
         let batches = make_batches_of_textchunk(chunk_iter, 1);
         assert!(!batches.is_empty());
     }
+
+    #[test]
+    fn test_chunk_iterator_sentence_overlap() {
+        let text = "Alice ran. Bob sat. Carol ate.";
+        let tokenized_text = tokenize(text);
+        let mut chunks: Vec<_> = ChunkIterator::new(&tokenized_text, 12, None)
+            .with_overlap(ChunkOverlap::Sentences(1))
+            .collect();
+        let texts: Vec<String> = chunks.iter_mut().map(|c| c.chunk_text().unwrap().to_string()).collect();
+        assert_eq!(texts, vec!["Alice ran.", "Alice ran. Bob sat.", "Bob sat. Carol ate."]);
+        assert!(chunks[0].overlap.is_none());
+        let overlap = chunks[2].overlap.as_ref().unwrap();
+        assert_eq!(tokens_text(&tokenized_text, overlap).unwrap(), "Bob sat.");
+    }
+
+    #[test]
+    fn test_chunk_iterator_character_overlap() {
+        let text = "Alice ran. Bob sat. Carol ate.";
+        let tokenized_text = tokenize(text);
+        let mut chunks: Vec<_> = ChunkIterator::new(&tokenized_text, 12, None)
+            .with_overlap(ChunkOverlap::Characters(5))
+            .collect();
+        let texts: Vec<String> = chunks.iter_mut().map(|c| c.chunk_text().unwrap().to_string()).collect();
+        assert_eq!(texts, vec!["Alice ran.", "ran. Bob sat.", "sat. Carol ate."]);
+    }
+
+    #[test]
+    fn test_chunk_iterator_sentence_starting_after_newline() {
+        let text = "I saw Bob\nStone today. Carol left.";
+        let tokenized_text = tokenize(text);
+        let chunks: Vec<_> = ChunkIterator::new(&tokenized_text, 10, None).take(20).collect();
+        assert!(chunks.len() < 20);
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.token_interval.start_index < c.token_interval.end_index)
+        );
+        assert_eq!(
+            chunks.last().unwrap().token_interval.end_index,
+            tokenized_text.tokens.len()
+        );
+    }
 }
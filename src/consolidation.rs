@@ -0,0 +1,646 @@
+/*!
+Consolidation of extractions into canonical entities.
+
+The annotator reports one extraction per mention, so "Dr. Smith", "Smith" and
+"he" in the same document are unrelated extractions. The consolidator clusters
+extractions of the same class into entities:
+
+1. Within a document, mentions with the same normalized text are grouped.
+2. Alias rules then fold groups into longer names (e.g. "Smith" into
+   "John Smith" when that is the only matching name of the class).
+3. An optional adjudicator, usually backed by a language model, decides the
+   remaining candidate pairs, such as pronouns or names sharing a word.
+4. Across documents, entities with the same class and normalized canonical
+   text are merged. Entities that are only a pronoun are kept per document,
+   since "he" in one document says nothing about "he" in another.
+
+Every entity gets an id derived from its class and canonical text, so the same
+entity gets the same id across runs and corpora; pronoun entities also include
+their document in the id.
+
+Usage example:
+    let entities = Consolidator::new().consolidate(&annotated_documents).await?;
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use async_trait::async_trait;
+
+use crate::data::{AnnotatedDocument, CharInterval};
+use crate::inference::{BaseLanguageModel, InferenceOutputError};
+
+const HONORIFICS: &[&str] = &["mr", "mrs", "ms", "miss", "dr", "prof", "sir", "st"];
+const PRONOUNS: &[&str] = &[
+    "he", "him", "his", "she", "her", "hers", "they", "them", "their", "theirs", "it", "its",
+];
+
+/// One extraction that refers to an entity.
+#[derive(Debug, Clone)]
+pub struct Mention {
+    pub document_id: String,
+    /// Position of the extraction in the document's `extractions`.
+    pub extraction_index: usize,
+    pub text: String,
+    pub char_interval: Option<CharInterval>,
+}
+
+/// A cluster of mentions that refer to the same real-world entity.
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub entity_id: String,
+    pub extraction_class: String,
+    pub canonical_text: String,
+    pub mentions: Vec<Mention>,
+}
+
+impl Entity {
+    /// Ids of the documents that mention this entity, in first-mention order.
+    pub fn document_ids(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.mentions
+            .iter()
+            .map(|mention| mention.document_id.as_str())
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+}
+
+/// Normalizes entity text for comparison: lowercases, drops punctuation and
+/// leading honorifics, and collapses whitespace.
+pub fn normalize_entity_text(text: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase();
+    let mut words: Vec<&str> = cleaned.split_whitespace().collect();
+    while words.len() > 1 && HONORIFICS.contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+fn is_pronoun(normalized: &str) -> bool {
+    PRONOUNS.contains(&normalized)
+}
+
+/// Decides whether a shorter name is an alias of a longer one. Both names are
+/// normalized with [`normalize_entity_text`].
+pub trait AliasRule: Send + Sync {
+    fn is_alias(&self, extraction_class: &str, alias: &str, name: &str) -> bool;
+}
+
+/// Treats a name as an alias when all its words appear in the longer name,
+/// e.g. "smith" for "john smith".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordSubsetAlias;
+
+impl AliasRule for WordSubsetAlias {
+    fn is_alias(&self, _extraction_class: &str, alias: &str, name: &str) -> bool {
+        let name_words: HashSet<&str> = name.split_whitespace().collect();
+        !alias.is_empty() && alias.split_whitespace().all(|word| name_words.contains(word))
+    }
+}
+
+/// Explicit aliases, e.g. "nyc" for "new york city", given as raw text.
+#[derive(Debug, Clone, Default)]
+pub struct AliasTable {
+    aliases: HashMap<String, String>,
+}
+
+impl AliasTable {
+    pub fn new<I, A, N>(aliases: I) -> Self
+    where
+        I: IntoIterator<Item = (A, N)>,
+        A: AsRef<str>,
+        N: AsRef<str>,
+    {
+        Self {
+            aliases: aliases
+                .into_iter()
+                .map(|(alias, name)| {
+                    (
+                        normalize_entity_text(alias.as_ref()),
+                        normalize_entity_text(name.as_ref()),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl AliasRule for AliasTable {
+    fn is_alias(&self, _extraction_class: &str, alias: &str, name: &str) -> bool {
+        self.aliases.get(alias).is_some_and(|target| target == name)
+    }
+}
+
+/// Decides whether two clusters of mentions refer to the same entity when the
+/// alias rules cannot.
+#[async_trait]
+pub trait EntityAdjudicator: Send + Sync {
+    async fn same_entity(
+        &self,
+        extraction_class: &str,
+        mentions: &[String],
+        candidate_mentions: &[String],
+        context: Option<&str>,
+    ) -> Result<bool, InferenceOutputError>;
+}
+
+/// Adjudicator that asks a language model a yes/no question.
+pub struct LlmAdjudicator<L: BaseLanguageModel> {
+    language_model: L,
+}
+
+impl<L: BaseLanguageModel> LlmAdjudicator<L> {
+    pub fn new(language_model: L) -> Self {
+        Self { language_model }
+    }
+
+    fn prompt(
+        extraction_class: &str,
+        mentions: &[String],
+        candidate_mentions: &[String],
+        context: Option<&str>,
+    ) -> String {
+        let mut prompt = String::new();
+        if let Some(context) = context {
+            let _ = writeln!(prompt, "Text:\n{}\n", context);
+        }
+        let _ = writeln!(
+            prompt,
+            "Do the mentions \"{}\" and \"{}\" refer to the same {}? Answer with yes or no.",
+            mentions.join("\", \""),
+            candidate_mentions.join("\", \""),
+            extraction_class
+        );
+        prompt
+    }
+}
+
+#[async_trait]
+impl<L: BaseLanguageModel> EntityAdjudicator for LlmAdjudicator<L> {
+    async fn same_entity(
+        &self,
+        extraction_class: &str,
+        mentions: &[String],
+        candidate_mentions: &[String],
+        context: Option<&str>,
+    ) -> Result<bool, InferenceOutputError> {
+        let prompt = Self::prompt(extraction_class, mentions, candidate_mentions, context);
+        let outputs = self.language_model.infer(&[prompt], None).await?;
+        let answer = outputs
+            .first()
+            .and_then(|scored| scored.first())
+            .and_then(|scored| scored.output.as_deref())
+            .unwrap_or_default();
+        Ok(answer.trim().to_lowercase().starts_with("yes"))
+    }
+}
+
+/// A group of mentions being built up during consolidation.
+#[derive(Debug, Clone)]
+struct Cluster {
+    extraction_class: String,
+    normalized: String,
+    mentions: Vec<Mention>,
+    /// Document the cluster is confined to, for clusters rooted at a pronoun.
+    document_scope: Option<String>,
+}
+
+impl Cluster {
+    fn mention_texts(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.mentions
+            .iter()
+            .map(|mention| mention.text.clone())
+            .filter(|text| seen.insert(text.clone()))
+            .collect()
+    }
+
+    fn into_entity(self) -> Entity {
+        let canonical_text = self
+            .mentions
+            .iter()
+            .map(|mention| mention.text.as_str())
+            .filter(|text| !is_pronoun(&normalize_entity_text(text)))
+            .fold(None::<&str>, |best, text| match best {
+                Some(best) if best.chars().count() >= text.chars().count() => Some(best),
+                _ => Some(text),
+            })
+            .unwrap_or(&self.mentions[0].text)
+            .to_string();
+        Entity {
+            entity_id: entity_id(&self.extraction_class, &self.normalized, self.document_scope.as_deref()),
+            extraction_class: self.extraction_class,
+            canonical_text,
+            mentions: self.mentions,
+        }
+    }
+}
+
+/// Id for a document without one, derived from its text so that its
+/// mentions get the same id on every run (FNV-1a).
+fn fallback_document_id(document: &AnnotatedDocument) -> String {
+    format!(
+        "doc_{:016x}",
+        fnv1a(document.text.as_deref().unwrap_or_default().bytes())
+    )
+}
+
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Stable id from the entity class, normalized canonical text and, for
+/// document-scoped entities, the document id (FNV-1a).
+fn entity_id(extraction_class: &str, normalized: &str, document_scope: Option<&str>) -> String {
+    let scope = document_scope.map(|id| [0].into_iter().chain(id.bytes()));
+    let bytes = extraction_class
+        .bytes()
+        .chain([0])
+        .chain(normalized.bytes())
+        .chain(scope.into_iter().flatten());
+    let hash = fnv1a(bytes);
+    format!("ent_{:016x}", hash)
+}
+
+/// Clusters extractions into entities within and across documents.
+pub struct Consolidator {
+    alias_rules: Vec<Box<dyn AliasRule>>,
+    adjudicator: Option<Box<dyn EntityAdjudicator>>,
+}
+
+impl Default for Consolidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Consolidator {
+    /// Creates a consolidator with the [`WordSubsetAlias`] rule.
+    pub fn new() -> Self {
+        Self {
+            alias_rules: vec![Box::new(WordSubsetAlias)],
+            adjudicator: None,
+        }
+    }
+
+    /// Creates a consolidator that only merges identical normalized text.
+    pub fn without_alias_rules() -> Self {
+        Self {
+            alias_rules: Vec::new(),
+            adjudicator: None,
+        }
+    }
+
+    pub fn with_alias_rule(mut self, alias_rule: impl AliasRule + 'static) -> Self {
+        self.alias_rules.push(Box::new(alias_rule));
+        self
+    }
+
+    /// Lets `adjudicator` decide pronouns and names that share a word but are
+    /// not aliases by rule.
+    pub fn with_adjudicator(mut self, adjudicator: impl EntityAdjudicator + 'static) -> Self {
+        self.adjudicator = Some(Box::new(adjudicator));
+        self
+    }
+
+    /// Consolidates the extractions of a single document.
+    pub async fn consolidate_document(
+        &self,
+        document: &AnnotatedDocument,
+    ) -> Result<Vec<Entity>, InferenceOutputError> {
+        self.consolidate(std::slice::from_ref(document)).await
+    }
+
+    /// Consolidates the extractions of a corpus. Entities are returned in
+    /// order of their first mention.
+    pub async fn consolidate(&self, documents: &[AnnotatedDocument]) -> Result<Vec<Entity>, InferenceOutputError> {
+        let mut corpus_clusters: Vec<Cluster> = Vec::new();
+        let mut corpus_index: HashMap<(String, String, Option<String>), usize> = HashMap::new();
+        for document in documents {
+            for cluster in self.consolidate_clusters(document).await? {
+                let key = (
+                    cluster.extraction_class.clone(),
+                    cluster.normalized.clone(),
+                    cluster.document_scope.clone(),
+                );
+                match corpus_index.get(&key) {
+                    Some(&index) => corpus_clusters[index].mentions.extend(cluster.mentions),
+                    None => {
+                        corpus_index.insert(key, corpus_clusters.len());
+                        corpus_clusters.push(cluster);
+                    }
+                }
+            }
+        }
+        Ok(corpus_clusters.into_iter().map(Cluster::into_entity).collect())
+    }
+
+    async fn consolidate_clusters(&self, document: &AnnotatedDocument) -> Result<Vec<Cluster>, InferenceOutputError> {
+        let document_id = match document.get_document_id() {
+            Some(document_id) => document_id.to_string(),
+            None => fallback_document_id(document),
+        };
+        let mut clusters: Vec<Cluster> = Vec::new();
+        let mut index: HashMap<(String, String), usize> = HashMap::new();
+        for (extraction_index, extraction) in document.extractions.iter().flatten().enumerate() {
+            let normalized = normalize_entity_text(&extraction.extraction_text);
+            if normalized.is_empty() {
+                continue;
+            }
+            let mention = Mention {
+                document_id: document_id.clone(),
+                extraction_index,
+                text: extraction.extraction_text.clone(),
+                char_interval: extraction.char_interval.clone(),
+            };
+            let key = (extraction.extraction_class.clone(), normalized.clone());
+            match index.get(&key) {
+                Some(&cluster_index) => clusters[cluster_index].mentions.push(mention),
+                None => {
+                    index.insert(key, clusters.len());
+                    clusters.push(Cluster {
+                        extraction_class: extraction.extraction_class.clone(),
+                        normalized,
+                        mentions: vec![mention],
+                        document_scope: None,
+                    });
+                }
+            }
+        }
+
+        // `merged_into[i]` is the cluster that cluster `i` was folded into.
+        let mut merged_into: Vec<Option<usize>> = vec![None; clusters.len()];
+        self.apply_alias_rules(&clusters, &mut merged_into);
+        if let Some(adjudicator) = &self.adjudicator {
+            self.adjudicate(
+                adjudicator.as_ref(),
+                document.text.as_deref(),
+                &clusters,
+                &mut merged_into,
+            )
+            .await?;
+        }
+
+        let root = |mut i: usize| {
+            while let Some(parent) = merged_into[i] {
+                i = parent;
+            }
+            i
+        };
+        let roots: Vec<usize> = (0..clusters.len()).map(root).collect();
+        let mut merged: Vec<Option<Cluster>> = clusters.into_iter().map(Some).collect();
+        for (i, &target) in roots.iter().enumerate() {
+            if target != i {
+                let mentions = merged[i].take().map(|cluster| cluster.mentions).unwrap_or_default();
+                if let Some(target) = merged[target].as_mut() {
+                    target.mentions.extend(mentions);
+                }
+            }
+        }
+        let mut clusters: Vec<Cluster> = merged.into_iter().flatten().collect();
+        for cluster in &mut clusters {
+            cluster.mentions.sort_by_key(|mention| mention.extraction_index);
+            if is_pronoun(&cluster.normalized) {
+                cluster.document_scope = Some(document_id.clone());
+            }
+        }
+        clusters.sort_by_key(|cluster| cluster.mentions[0].extraction_index);
+        Ok(clusters)
+    }
+
+    /// Folds each name into the single longer name of its class it is an
+    /// alias of. Ambiguous aliases are left alone.
+    fn apply_alias_rules(&self, clusters: &[Cluster], merged_into: &mut [Option<usize>]) {
+        for (i, cluster) in clusters.iter().enumerate() {
+            if is_pronoun(&cluster.normalized) {
+                continue;
+            }
+            let targets: Vec<usize> = clusters
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| {
+                    *j != i
+                        && other.extraction_class == cluster.extraction_class
+                        && other.normalized.len() > cluster.normalized.len()
+                        && self.alias_rules.iter().any(|rule| {
+                            rule.is_alias(&cluster.extraction_class, &cluster.normalized, &other.normalized)
+                        })
+                })
+                .map(|(j, _)| j)
+                .collect();
+            if let [target] = targets[..] {
+                merged_into[i] = Some(target);
+            }
+        }
+    }
+
+    /// Asks the adjudicator about pronouns and about names sharing a word,
+    /// merging each cluster into the first earlier cluster it agrees with.
+    async fn adjudicate(
+        &self,
+        adjudicator: &dyn EntityAdjudicator,
+        context: Option<&str>,
+        clusters: &[Cluster],
+        merged_into: &mut [Option<usize>],
+    ) -> Result<(), InferenceOutputError> {
+        for i in 0..clusters.len() {
+            if merged_into[i].is_some() {
+                continue;
+            }
+            let cluster = &clusters[i];
+            let words: HashSet<&str> = cluster.normalized.split_whitespace().collect();
+            for j in (0..i).rev() {
+                let candidate = &clusters[j];
+                if merged_into[j].is_some() || candidate.extraction_class != cluster.extraction_class {
+                    continue;
+                }
+                let shares_word = candidate.normalized.split_whitespace().any(|word| words.contains(word));
+                if !is_pronoun(&cluster.normalized) && !shares_word {
+                    continue;
+                }
+                if is_pronoun(&candidate.normalized) {
+                    continue;
+                }
+                if adjudicator
+                    .same_entity(
+                        &cluster.extraction_class,
+                        &cluster.mention_texts(),
+                        &candidate.mention_texts(),
+                        context,
+                    )
+                    .await?
+                {
+                    merged_into[i] = Some(j);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Extraction;
+
+    fn document(document_id: &str, mentions: &[(&str, &str)]) -> AnnotatedDocument {
+        let extractions = mentions
+            .iter()
+            .map(|(class, text)| {
+                Extraction::new(
+                    class.to_string(),
+                    text.to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            })
+            .collect();
+        AnnotatedDocument::new(Some(document_id.to_string()), Some(extractions), None)
+    }
+
+    fn summary(entities: &[Entity]) -> Vec<(String, Vec<usize>)> {
+        entities
+            .iter()
+            .map(|e| {
+                (
+                    e.canonical_text.clone(),
+                    e.mentions.iter().map(|m| m.extraction_index).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_entity_text() {
+        assert_eq!(normalize_entity_text("Dr. John  Smith,"), "john smith");
+        assert_eq!(normalize_entity_text("Dr"), "dr");
+    }
+
+    #[tokio::test]
+    async fn test_consolidate_document_with_alias_rules() {
+        let doc = document(
+            "doc_1",
+            &[
+                ("person", "Dr. Smith"),
+                ("person", "Alice"),
+                ("person", "John Smith"),
+                ("person", "Smith"),
+                ("place", "Smith"),
+                ("person", "he"),
+            ],
+        );
+        let entities = Consolidator::new().consolidate_document(&doc).await.unwrap();
+        assert_eq!(
+            summary(&entities),
+            vec![
+                ("John Smith".to_string(), vec![0, 2, 3]),
+                ("Alice".to_string(), vec![1]),
+                ("Smith".to_string(), vec![4]),
+                ("he".to_string(), vec![5]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ambiguous_alias_is_not_merged() {
+        let doc = document(
+            "doc_1",
+            &[("person", "John Smith"), ("person", "Jane Smith"), ("person", "Smith")],
+        );
+        let entities = Consolidator::new().consolidate_document(&doc).await.unwrap();
+        assert_eq!(entities.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_entity_ids_are_stable_across_documents() {
+        let docs = vec![
+            document("doc_1", &[("person", "Alice Jones"), ("person", "Jones")]),
+            document("doc_2", &[("person", "Bob"), ("person", "alice jones")]),
+        ];
+        let entities = Consolidator::new().consolidate(&docs).await.unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].document_ids(), vec!["doc_1", "doc_2"]);
+        assert_eq!(entities[0].mentions.len(), 3);
+
+        let again = Consolidator::new().consolidate(&docs[1..]).await.unwrap();
+        assert_eq!(again[1].entity_id, entities[0].entity_id);
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_pronouns_are_not_merged_across_documents() {
+        let docs = vec![
+            document("doc_1", &[("person", "Alice"), ("person", "she")]),
+            document("doc_2", &[("person", "Bob"), ("person", "She")]),
+        ];
+        let entities = Consolidator::new().consolidate(&docs).await.unwrap();
+        let pronouns: Vec<&Entity> = entities
+            .iter()
+            .filter(|e| e.canonical_text.eq_ignore_ascii_case("she"))
+            .collect();
+        assert_eq!(pronouns.len(), 2);
+        assert_eq!(pronouns[0].document_ids(), vec!["doc_1"]);
+        assert_eq!(pronouns[1].document_ids(), vec!["doc_2"]);
+        assert_ne!(pronouns[0].entity_id, pronouns[1].entity_id);
+    }
+
+    #[tokio::test]
+    async fn test_documents_without_id_get_stable_mention_ids() {
+        let mut doc = document("unused", &[("person", "Alice")]);
+        doc.set_document_id(None);
+        doc.text = Some("Alice went home.".to_string());
+
+        let first = Consolidator::new().consolidate_document(&doc).await.unwrap();
+        let second = Consolidator::new().consolidate_document(&doc).await.unwrap();
+        assert_eq!(first[0].document_ids(), second[0].document_ids());
+        assert!(first[0].mentions[0].document_id.starts_with("doc_"));
+        assert!(doc.get_document_id().is_none());
+    }
+
+    struct PronounAdjudicator;
+
+    #[async_trait]
+    impl EntityAdjudicator for PronounAdjudicator {
+        async fn same_entity(
+            &self,
+            _extraction_class: &str,
+            mentions: &[String],
+            candidate_mentions: &[String],
+            _context: Option<&str>,
+        ) -> Result<bool, InferenceOutputError> {
+            Ok(mentions == ["he"] && candidate_mentions.contains(&"Dr. Smith".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adjudicator_links_pronouns() {
+        let doc = document(
+            "doc_1",
+            &[("person", "Dr. Smith"), ("person", "Alice"), ("person", "he")],
+        );
+        let entities = Consolidator::new()
+            .with_adjudicator(PronounAdjudicator)
+            .consolidate_document(&doc)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary(&entities),
+            vec![("Dr. Smith".to_string(), vec![0, 2]), ("Alice".to_string(), vec![1])]
+        );
+    }
+}
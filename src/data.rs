@@ -10,6 +10,8 @@ pub enum AlignmentStatus {
     MatchGreater,
     MatchLesser,
     MatchFuzzy,
+    /// No span matched; attributed to the most similar sentence instead.
+    MatchSentence,
 }
 
 impl fmt::Display for AlignmentStatus {
@@ -19,6 +21,7 @@ impl fmt::Display for AlignmentStatus {
             AlignmentStatus::MatchGreater => write!(f, "match_greater"),
             AlignmentStatus::MatchLesser => write!(f, "match_lesser"),
             AlignmentStatus::MatchFuzzy => write!(f, "match_fuzzy"),
+            AlignmentStatus::MatchSentence => write!(f, "match_sentence"),
         }
     }
 }
@@ -32,12 +35,13 @@ impl TryFrom<&str> for AlignmentStatus {
             "match_greater" => Ok(AlignmentStatus::MatchGreater),
             "match_lesser" => Ok(AlignmentStatus::MatchLesser),
             "match_fuzzy" => Ok(AlignmentStatus::MatchFuzzy),
+            "match_sentence" => Ok(AlignmentStatus::MatchSentence),
             _ => Err(format!("Unknown alignment status: {}", s)),
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct CharInterval {
     pub start_pos: Option<usize>,
     pub end_pos: Option<usize>,
@@ -59,6 +63,19 @@ pub struct Extraction {
     pub group_index: Option<usize>,
     pub description: Option<String>,
     pub attributes: Option<HashMap<String, AttributeValue>>,
+    /// Confidence in `[0, 1]`, when a merge, vote or verification step
+    /// assigned one.
+    pub confidence: Option<f64>,
+    /// Why a verification step judged the extraction as it did.
+    pub rationale: Option<String>,
+    /// The models whose output contained the extraction.
+    pub model_ids: Vec<String>,
+    /// The extraction repeats an earlier one after every occurrence of its
+    /// text was taken, and shares that extraction's span.
+    pub duplicate: bool,
+    /// Levenshtein distance between the extraction text and its source span,
+    /// when character-level fuzzy matching aligned it.
+    pub edit_distance: Option<usize>,
     token_interval: Option<TokenInterval>,
 }
 
@@ -91,6 +108,11 @@ impl Extraction {
             group_index,
             description,
             attributes,
+            confidence: None,
+            rationale: None,
+            model_ids: Vec::new(),
+            duplicate: false,
+            edit_distance: None,
         }
     }
 
@@ -144,11 +166,151 @@ impl Document {
     }
 }
 
+/// Why a chunk produced no extractions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkErrorKind {
+    /// The language model call failed.
+    Inference,
+    /// The language model returned no output for the chunk.
+    EmptyOutput,
+    /// The output could not be parsed into extractions.
+    Parse,
+    /// The output hit the model's output limit and splitting the chunk did
+    /// not recover it.
+    Truncated,
+}
+
+impl fmt::Display for ChunkErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChunkErrorKind::Inference => write!(f, "inference"),
+            ChunkErrorKind::EmptyOutput => write!(f, "empty_output"),
+            ChunkErrorKind::Parse => write!(f, "parse"),
+            ChunkErrorKind::Truncated => write!(f, "truncated"),
+        }
+    }
+}
+
+impl TryFrom<&str> for ChunkErrorKind {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "inference" => Ok(ChunkErrorKind::Inference),
+            "empty_output" => Ok(ChunkErrorKind::EmptyOutput),
+            "parse" => Ok(ChunkErrorKind::Parse),
+            "truncated" => Ok(ChunkErrorKind::Truncated),
+            _ => Err(format!("Unknown chunk error kind: {}", s)),
+        }
+    }
+}
+
+/// A chunk of a document that could not be annotated, so its text is not
+/// covered by the document's extractions.
+#[derive(Debug, Clone)]
+pub struct ChunkFailure {
+    pub char_interval: CharInterval,
+    pub error_kind: ChunkErrorKind,
+    pub message: String,
+    /// The model output that failed to parse, when there was one.
+    pub raw_output: Option<String>,
+    /// Complete extractions salvaged from truncated output, used if the
+    /// chunk cannot be split to recover the rest.
+    pub(crate) salvaged: Option<Vec<Extraction>>,
+}
+
+impl ChunkFailure {
+    pub fn new(
+        char_interval: CharInterval,
+        error_kind: ChunkErrorKind,
+        message: impl Into<String>,
+        raw_output: Option<String>,
+    ) -> Self {
+        Self {
+            char_interval,
+            error_kind,
+            message: message.into(),
+            raw_output,
+            salvaged: None,
+        }
+    }
+}
+
+impl fmt::Display for ChunkFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} failure for chunk [{}, {}): {}",
+            self.error_kind,
+            self.char_interval.start_pos.unwrap_or(0),
+            self.char_interval.end_pos.unwrap_or(0),
+            self.message
+        )
+    }
+}
+
+/// One attempt to have the model correct output that failed to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairAttempt {
+    /// The output that failed to parse.
+    pub output: String,
+    /// The parser error sent back to the model with it.
+    pub error: String,
+}
+
+/// What happened while annotating a chunk in one pass, beyond the extractions
+/// it produced.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChunkDiagnostics {
+    pub char_interval: CharInterval,
+    pub pass_index: usize,
+    /// Outputs sent back to the model for repair, in order.
+    pub repair_attempts: Vec<RepairAttempt>,
+    /// The output that parsed after repair, when repair succeeded.
+    pub repaired_output: Option<String>,
+    /// Fixes lenient parsing applied to the output, such as removed trailing
+    /// commas or a dropped incomplete item.
+    pub parse_repairs: Vec<String>,
+    /// The reasoning trace of a reasoning model, when kept.
+    pub reasoning: Option<String>,
+    /// Parts of a chunk split after truncated output that still failed;
+    /// their text has no extractions, unlike the rest of the chunk.
+    pub split_failures: Vec<String>,
+}
+
+impl ChunkDiagnostics {
+    pub fn new(char_interval: CharInterval, pass_index: usize) -> Self {
+        Self {
+            char_interval,
+            pass_index,
+            ..Self::default()
+        }
+    }
+
+    /// Whether there is nothing to report for the chunk.
+    pub fn is_empty(&self) -> bool {
+        self.repair_attempts.is_empty()
+            && self.repaired_output.is_none()
+            && self.parse_repairs.is_empty()
+            && self.reasoning.is_none()
+            && self.split_failures.is_empty()
+    }
+}
+
 /// AnnotatedDocument 结构体
 #[derive(Debug, Clone)]
 pub struct AnnotatedDocument {
     pub extractions: Option<Vec<Extraction>>,
     pub text: Option<String>,
+    /// Chunks that failed and were skipped; their text has no extractions.
+    pub chunk_failures: Vec<ChunkFailure>,
+    /// Chunks a relevance filter judged to contain nothing to extract.
+    pub skipped_chunks: Vec<CharInterval>,
+    /// Chunks with something to report, such as repaired output.
+    pub chunk_diagnostics: Vec<ChunkDiagnostics>,
+    /// Why the verifier could not check the extractions, which are then kept
+    /// unverified.
+    pub verification_error: Option<String>,
     document_id: Option<String>,
     tokenized_text: Option<TokenizedText>,
 }
@@ -158,6 +320,10 @@ impl AnnotatedDocument {
         Self {
             extractions,
             text,
+            chunk_failures: Vec::new(),
+            skipped_chunks: Vec::new(),
+            chunk_diagnostics: Vec::new(),
+            verification_error: None,
             document_id,
             tokenized_text: None,
         }
@@ -170,6 +336,11 @@ impl AnnotatedDocument {
         self.document_id.clone().unwrap()
     }
 
+    /// The document id, if one is set, without generating one.
+    pub fn get_document_id(&self) -> Option<&str> {
+        self.document_id.as_deref()
+    }
+
     pub fn set_document_id(&mut self, value: Option<String>) {
         self.document_id = value;
     }
@@ -298,6 +469,21 @@ mod tests {
         assert_eq!(example.extractions.len(), 1);
     }
 
+    #[test]
+    fn test_chunk_failure_display() {
+        let failure = ChunkFailure::new(
+            CharInterval::new(Some(10), Some(42)),
+            ChunkErrorKind::Parse,
+            "invalid YAML",
+            Some("not: [yaml".to_string()),
+        );
+        assert_eq!(failure.to_string(), "parse failure for chunk [10, 42): invalid YAML");
+        assert_eq!(
+            ChunkErrorKind::try_from("empty_output").unwrap(),
+            ChunkErrorKind::EmptyOutput
+        );
+    }
+
     #[test]
     fn test_alignment_status_conversion() {
         let status_str = AlignmentStatus::MatchExact.to_string();
@@ -305,5 +491,8 @@ mod tests {
 
         let status = AlignmentStatus::try_from("match_fuzzy").unwrap();
         assert_eq!(status, AlignmentStatus::MatchFuzzy);
+
+        let status = AlignmentStatus::try_from(AlignmentStatus::MatchSentence.to_string().as_str()).unwrap();
+        assert_eq!(status, AlignmentStatus::MatchSentence);
     }
 }
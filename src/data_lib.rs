@@ -1,9 +1,98 @@
-use serde_json::{Map, Value};
+use serde_json::{Map, Value, json};
 use std::collections::HashMap;
 
-use crate::data::{AnnotatedDocument, AttributeValue, CharInterval, Extraction};
+use crate::data::{
+    AnnotatedDocument, AttributeValue, CharInterval, ChunkDiagnostics, ChunkErrorKind, ChunkFailure, Extraction,
+    RepairAttempt,
+};
 use crate::tokenizer::TokenInterval;
 
+/// Converts a single extraction into its JSON representation.
+pub fn extraction_to_dict(ext: &Extraction) -> Value {
+    let mut ext_map = Map::new();
+    ext_map.insert(
+        "extraction_class".to_string(),
+        Value::String(ext.extraction_class.clone()),
+    );
+    ext_map.insert(
+        "extraction_text".to_string(),
+        Value::String(ext.extraction_text.clone()),
+    );
+
+    // alignment_status
+    if let Some(status) = &ext.alignment_status {
+        ext_map.insert("alignment_status".to_string(), Value::String(status.to_string()));
+    }
+
+    // char_interval
+    if let Some(ref char_interval) = ext.char_interval {
+        let mut ci = Map::new();
+        if let Some(start) = char_interval.start_pos {
+            ci.insert("start_pos".to_string(), Value::Number(start.into()));
+        }
+        if let Some(end) = char_interval.end_pos {
+            ci.insert("end_pos".to_string(), Value::Number(end.into()));
+        }
+        ext_map.insert("char_interval".to_string(), Value::Object(ci));
+    }
+
+    // token_interval
+    if let Some(token_interval) = ext.token_interval() {
+        let mut ti = Map::new();
+        ti.insert("start".to_string(), Value::Number(token_interval.start_index.into()));
+        ti.insert("end".to_string(), Value::Number(token_interval.end_index.into()));
+        ext_map.insert("token_interval".to_string(), Value::Object(ti));
+    }
+
+    // extraction_index / group_index / description
+    if let Some(index) = ext.extraction_index {
+        ext_map.insert("extraction_index".to_string(), Value::Number(index.into()));
+    }
+    if let Some(index) = ext.group_index {
+        ext_map.insert("group_index".to_string(), Value::Number(index.into()));
+    }
+    if let Some(ref description) = ext.description {
+        ext_map.insert("description".to_string(), Value::String(description.clone()));
+    }
+    if let Some(confidence) = ext.confidence.and_then(serde_json::Number::from_f64) {
+        ext_map.insert("confidence".to_string(), Value::Number(confidence));
+    }
+    if let Some(ref rationale) = ext.rationale {
+        ext_map.insert("rationale".to_string(), Value::String(rationale.clone()));
+    }
+    if !ext.model_ids.is_empty() {
+        let model_ids = ext.model_ids.iter().cloned().map(Value::String).collect();
+        ext_map.insert("model_ids".to_string(), Value::Array(model_ids));
+    }
+    if ext.duplicate {
+        ext_map.insert("duplicate".to_string(), Value::Bool(true));
+    }
+    if let Some(distance) = ext.edit_distance {
+        ext_map.insert("edit_distance".to_string(), Value::Number(distance.into()));
+    }
+
+    // attributes
+    if let Some(ref attrs) = ext.attributes {
+        let mut attr_map = Map::new();
+        for (k, v) in attrs {
+            match v {
+                AttributeValue::Single(s) => {
+                    attr_map.insert(k.clone(), Value::String(s.clone()));
+                }
+                AttributeValue::Multiple(list) => {
+                    attr_map.insert(
+                        k.clone(),
+                        Value::Array(list.iter().map(|s| Value::String(s.clone())).collect()),
+                    );
+                }
+            }
+        }
+        ext_map.insert("attributes".to_string(), Value::Object(attr_map));
+    }
+
+    Value::Object(ext_map)
+}
+
 pub fn annotated_document_to_dict(adoc: &AnnotatedDocument) -> Value {
     if adoc.text.is_none() && adoc.extractions.is_none() {
         return Value::Object(Map::new());
@@ -23,68 +112,197 @@ pub fn annotated_document_to_dict(adoc: &AnnotatedDocument) -> Value {
     if let Some(ref extractions) = adoc.extractions {
         let mut ext_array = Vec::new();
         for ext in extractions {
-            let mut ext_map = Map::new();
-            ext_map.insert(
-                "extraction_class".to_string(),
-                Value::String(ext.extraction_class.clone()),
-            );
-            ext_map.insert(
-                "extraction_text".to_string(),
-                Value::String(ext.extraction_text.clone()),
-            );
-
-            // alignment_status
-            if let Some(status) = &ext.alignment_status {
-                ext_map.insert("alignment_status".to_string(), Value::String(status.to_string()));
-            }
+            ext_array.push(extraction_to_dict(ext));
+        }
+        map.insert("extractions".to_string(), Value::Array(ext_array));
+    }
 
-            // char_interval
-            if let Some(ref char_interval) = ext.char_interval {
+    // chunk_failures
+    if !adoc.chunk_failures.is_empty() {
+        let failures = adoc
+            .chunk_failures
+            .iter()
+            .map(|failure| {
+                let mut failure_map = Map::new();
                 let mut ci = Map::new();
-                if let Some(start) = char_interval.start_pos {
+                if let Some(start) = failure.char_interval.start_pos {
                     ci.insert("start_pos".to_string(), Value::Number(start.into()));
                 }
-                if let Some(end) = char_interval.end_pos {
+                if let Some(end) = failure.char_interval.end_pos {
                     ci.insert("end_pos".to_string(), Value::Number(end.into()));
                 }
-                ext_map.insert("char_interval".to_string(), Value::Object(ci));
-            }
+                failure_map.insert("char_interval".to_string(), Value::Object(ci));
+                failure_map.insert("error_kind".to_string(), Value::String(failure.error_kind.to_string()));
+                failure_map.insert("message".to_string(), Value::String(failure.message.clone()));
+                if let Some(ref raw_output) = failure.raw_output {
+                    failure_map.insert("raw_output".to_string(), Value::String(raw_output.clone()));
+                }
+                Value::Object(failure_map)
+            })
+            .collect();
+        map.insert("chunk_failures".to_string(), Value::Array(failures));
+    }
 
-            // token_interval
-            if let Some(token_interval) = ext.token_interval() {
-                let mut ti = Map::new();
-                ti.insert("start".to_string(), Value::Number(token_interval.start_index.into()));
-                ti.insert("end".to_string(), Value::Number(token_interval.end_index.into()));
-                ext_map.insert("token_interval".to_string(), Value::Object(ti));
-            }
+    // skipped_chunks
+    if !adoc.skipped_chunks.is_empty() {
+        let skipped = adoc
+            .skipped_chunks
+            .iter()
+            .map(|interval| {
+                let mut ci = Map::new();
+                if let Some(start) = interval.start_pos {
+                    ci.insert("start_pos".to_string(), Value::Number(start.into()));
+                }
+                if let Some(end) = interval.end_pos {
+                    ci.insert("end_pos".to_string(), Value::Number(end.into()));
+                }
+                Value::Object(ci)
+            })
+            .collect();
+        map.insert("skipped_chunks".to_string(), Value::Array(skipped));
+    }
 
-            // attributes
-            if let Some(ref attrs) = ext.attributes {
-                let mut attr_map = Map::new();
-                for (k, v) in attrs {
-                    match v {
-                        AttributeValue::Single(s) => {
-                            attr_map.insert(k.clone(), Value::String(s.clone()));
-                        }
-                        AttributeValue::Multiple(list) => {
-                            attr_map.insert(
-                                k.clone(),
-                                Value::Array(list.iter().map(|s| Value::String(s.clone())).collect()),
-                            );
-                        }
-                    }
+    // chunk_diagnostics
+    if !adoc.chunk_diagnostics.is_empty() {
+        let diagnostics = adoc
+            .chunk_diagnostics
+            .iter()
+            .map(|diagnostics| {
+                let repair_attempts: Vec<Value> = diagnostics
+                    .repair_attempts
+                    .iter()
+                    .map(|attempt| json!({"output": attempt.output, "error": attempt.error}))
+                    .collect();
+                let mut diagnostics_map = json!({
+                    "char_interval": {
+                        "start_pos": diagnostics.char_interval.start_pos,
+                        "end_pos": diagnostics.char_interval.end_pos,
+                    },
+                    "pass_index": diagnostics.pass_index,
+                    "repair_attempts": repair_attempts,
+                });
+                if let Some(ref repaired_output) = diagnostics.repaired_output {
+                    diagnostics_map["repaired_output"] = Value::String(repaired_output.clone());
                 }
-                ext_map.insert("attributes".to_string(), Value::Object(attr_map));
-            }
+                if let Some(ref reasoning) = diagnostics.reasoning {
+                    diagnostics_map["reasoning"] = Value::String(reasoning.clone());
+                }
+                if !diagnostics.parse_repairs.is_empty() {
+                    diagnostics_map["parse_repairs"] = json!(diagnostics.parse_repairs);
+                }
+                if !diagnostics.split_failures.is_empty() {
+                    diagnostics_map["split_failures"] = json!(diagnostics.split_failures);
+                }
+                diagnostics_map
+            })
+            .collect();
+        map.insert("chunk_diagnostics".to_string(), Value::Array(diagnostics));
+    }
 
-            ext_array.push(Value::Object(ext_map));
-        }
-        map.insert("extractions".to_string(), Value::Array(ext_array));
+    // verification_error
+    if let Some(ref verification_error) = adoc.verification_error {
+        map.insert(
+            "verification_error".to_string(),
+            Value::String(verification_error.clone()),
+        );
     }
 
     Value::Object(map)
 }
 
+/// Parses a single extraction from its JSON representation.
+///
+/// Returns `None` if `value` is not an object or has an incomplete
+/// `token_interval`.
+pub fn dict_to_extraction(value: &Value) -> Option<Extraction> {
+    let ext_obj = value.as_object()?;
+
+    let extraction_class = ext_obj
+        .get("extraction_class")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let extraction_text = ext_obj
+        .get("extraction_text")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    // alignment_status
+    let alignment_status = ext_obj
+        .get("alignment_status")
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.try_into().ok());
+
+    // char_interval
+    let char_interval = ext_obj.get("char_interval").map(|ci| {
+        let start = ci.get("start_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+        let end = ci.get("end_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+        CharInterval::new(start, end)
+    });
+
+    // token_interval
+    let token_interval = match ext_obj.get("token_interval") {
+        Some(ti) => Some(TokenInterval {
+            start_index: ti.get("start").and_then(|v| v.as_u64())? as usize,
+            end_index: ti.get("end").and_then(|v| v.as_u64())? as usize,
+        }),
+        None => None,
+    };
+
+    let extraction_index = ext_obj
+        .get("extraction_index")
+        .and_then(|v| v.as_u64())
+        .map(|x| x as usize);
+    let group_index = ext_obj.get("group_index").and_then(|v| v.as_u64()).map(|x| x as usize);
+    let description = ext_obj
+        .get("description")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    // attributes
+    let attributes = ext_obj.get("attributes").map(|attrs| {
+        let mut map = HashMap::new();
+        if let Some(obj) = attrs.as_object() {
+            for (k, v) in obj {
+                if v.is_string() {
+                    map.insert(k.clone(), AttributeValue::Single(v.as_str().unwrap().to_string()));
+                } else if v.is_array() {
+                    let arr = v.as_array().unwrap();
+                    let vec_str: Vec<String> = arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
+                    map.insert(k.clone(), AttributeValue::Multiple(vec_str));
+                }
+            }
+        }
+        map
+    });
+
+    let mut extraction = Extraction::new(
+        extraction_class,
+        extraction_text,
+        token_interval,
+        char_interval,
+        alignment_status,
+        extraction_index,
+        group_index,
+        description,
+        attributes,
+    );
+    extraction.confidence = ext_obj.get("confidence").and_then(|v| v.as_f64());
+    extraction.rationale = ext_obj.get("rationale").and_then(|v| v.as_str()).map(|s| s.to_string());
+    extraction.model_ids = ext_obj
+        .get("model_ids")
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| id.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    extraction.duplicate = ext_obj.get("duplicate").and_then(|v| v.as_bool()).unwrap_or(false);
+    extraction.edit_distance = ext_obj
+        .get("edit_distance")
+        .and_then(|v| v.as_u64())
+        .map(|d| d as usize);
+    Some(extraction)
+}
+
 pub fn dict_to_annotated_document(value: &Value) -> AnnotatedDocument {
     if !value.is_object() {
         return AnnotatedDocument::new(None, None, None);
@@ -98,87 +316,123 @@ pub fn dict_to_annotated_document(value: &Value) -> AnnotatedDocument {
     let extractions = map
         .get("extractions")
         .and_then(|v| v.as_array())
-        .map(|extractions| {
-            extractions
-                .iter()
-                .filter_map(|ext_val| {
-                    let ext_obj = ext_val.as_object()?;
-
-                    let extraction_class = ext_obj
-                        .get("extraction_class")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    let extraction_text = ext_obj
-                        .get("extraction_text")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    // alignment_status
-                    let alignment_status = ext_obj
-                        .get("alignment_status")
-                        .and_then(|v| v.as_str())
-                        .and_then(|s| s.try_into().ok());
+        .map(|extractions| extractions.iter().filter_map(dict_to_extraction).collect::<Vec<_>>())
+        .filter(|v| !v.is_empty());
 
-                    // char_interval
-                    let char_interval = ext_obj.get("char_interval").map(|ci| {
+    let chunk_failures = map
+        .get("chunk_failures")
+        .and_then(|v| v.as_array())
+        .map(|failures| {
+            failures
+                .iter()
+                .filter_map(|failure_val| {
+                    let failure_obj = failure_val.as_object()?;
+                    let char_interval = failure_obj.get("char_interval").map(|ci| {
                         let start = ci.get("start_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
                         let end = ci.get("end_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
                         CharInterval::new(start, end)
-                    });
-
-                    // token_interval
-                    let token_interval = ext_obj.get("token_interval").map(|ti| {
-                        let start = ti.get("start").and_then(|v| v.as_u64()).map(|x| x as usize);
-                        let end = ti.get("end").and_then(|v| v.as_u64()).map(|x| x as usize);
-                        TokenInterval {
-                            start_index: start.unwrap(),
-                            end_index: end.unwrap(),
-                        }
-                    });
-
-                    // attributes
-                    let attributes = ext_obj.get("attributes").map(|attrs| {
-                        let mut map = HashMap::new();
-                        if let Some(obj) = attrs.as_object() {
-                            for (k, v) in obj {
-                                if v.is_string() {
-                                    map.insert(k.clone(), AttributeValue::Single(v.as_str().unwrap().to_string()));
-                                } else if v.is_array() {
-                                    let arr = v.as_array().unwrap();
-                                    let vec_str: Vec<String> =
-                                        arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
-                                    map.insert(k.clone(), AttributeValue::Multiple(vec_str));
-                                }
-                            }
-                        }
-                        map
-                    });
-
-                    Some(Extraction::new(
-                        extraction_class,
-                        extraction_text,
-                        token_interval,
-                        char_interval,
-                        alignment_status,
-                        None,
-                        None,
-                        None,
-                        attributes,
-                    ))
+                    })?;
+                    let error_kind = failure_obj
+                        .get("error_kind")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| ChunkErrorKind::try_from(s).ok())?;
+                    let message = failure_obj.get("message").and_then(|v| v.as_str()).unwrap_or("");
+                    let raw_output = failure_obj
+                        .get("raw_output")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string());
+                    Some(ChunkFailure::new(char_interval, error_kind, message, raw_output))
                 })
-                .collect::<Vec<_>>()
+                .collect()
         })
-        .filter(|v| !v.is_empty());
+        .unwrap_or_default();
+
+    let skipped_chunks = map
+        .get("skipped_chunks")
+        .and_then(|v| v.as_array())
+        .map(|skipped| {
+            skipped
+                .iter()
+                .map(|ci| {
+                    let start = ci.get("start_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+                    let end = ci.get("end_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+                    CharInterval::new(start, end)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut adoc = AnnotatedDocument::new(document_id, extractions, text);
+    adoc.chunk_failures = chunk_failures;
+    adoc.skipped_chunks = skipped_chunks;
+    adoc.chunk_diagnostics = map
+        .get("chunk_diagnostics")
+        .and_then(|v| v.as_array())
+        .map(|diagnostics| diagnostics.iter().filter_map(dict_to_chunk_diagnostics).collect())
+        .unwrap_or_default();
+    adoc.verification_error = map
+        .get("verification_error")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    adoc
+}
 
-    AnnotatedDocument::new(document_id, extractions, text)
+fn dict_to_chunk_diagnostics(value: &Value) -> Option<ChunkDiagnostics> {
+    let ci = value.get("char_interval")?;
+    let start = ci.get("start_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+    let end = ci.get("end_pos").and_then(|v| v.as_u64()).map(|x| x as usize);
+    let pass_index = value.get("pass_index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let mut diagnostics = ChunkDiagnostics::new(CharInterval::new(start, end), pass_index);
+    diagnostics.repair_attempts = value
+        .get("repair_attempts")
+        .and_then(|v| v.as_array())
+        .map(|attempts| {
+            attempts
+                .iter()
+                .filter_map(|attempt| {
+                    Some(RepairAttempt {
+                        output: attempt.get("output")?.as_str()?.to_string(),
+                        error: attempt.get("error")?.as_str()?.to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    diagnostics.repaired_output = value
+        .get("repaired_output")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    diagnostics.reasoning = value.get("reasoning").and_then(|v| v.as_str()).map(|s| s.to_string());
+    diagnostics.parse_repairs = value
+        .get("parse_repairs")
+        .and_then(|v| v.as_array())
+        .map(|repairs| {
+            repairs
+                .iter()
+                .filter_map(|r| r.as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    diagnostics.split_failures = value
+        .get("split_failures")
+        .and_then(|v| v.as_array())
+        .map(|failures| {
+            failures
+                .iter()
+                .filter_map(|f| f.as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(diagnostics)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data::{AlignmentStatus, AnnotatedDocument, AttributeValue, CharInterval, Extraction};
+    use crate::data::{
+        AlignmentStatus, AnnotatedDocument, AttributeValue, CharInterval, ChunkDiagnostics, ChunkErrorKind,
+        ChunkFailure, Extraction, RepairAttempt,
+    };
     use crate::tokenizer::TokenInterval;
     use std::collections::HashMap;
 
@@ -192,7 +446,7 @@ mod tests {
         let mut attributes = HashMap::new();
         attributes.insert("attr1".to_string(), AttributeValue::Single("value1".to_string()));
 
-        let extraction = Extraction::new(
+        let mut extraction = Extraction::new(
             "class1".to_string(),
             "text1".to_string(),
             Some(token_interval.clone()),
@@ -203,6 +457,8 @@ mod tests {
             None,
             Some(attributes.clone()),
         );
+        extraction.duplicate = true;
+        extraction.edit_distance = Some(2);
 
         let adoc = AnnotatedDocument::new(
             Some("doc_1234".to_string()),
@@ -216,7 +472,66 @@ mod tests {
         let adoc_back = dict_to_annotated_document(&dict);
 
         assert_eq!(adoc_back.text.unwrap(), "hello".to_string());
-        assert_eq!(adoc_back.extractions.unwrap().len(), 1);
+        let extractions = adoc_back.extractions.unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert!(extractions[0].duplicate);
+        assert_eq!(extractions[0].edit_distance, Some(2));
+    }
+
+    #[test]
+    fn test_chunk_failures_round_trip() {
+        let mut adoc = AnnotatedDocument::new(Some("doc_1".to_string()), Some(vec![]), Some("hello".to_string()));
+        adoc.chunk_failures.push(ChunkFailure::new(
+            CharInterval::new(Some(0), Some(5)),
+            ChunkErrorKind::Parse,
+            "bad output",
+            Some("```yaml\n[".to_string()),
+        ));
+
+        let adoc_back = dict_to_annotated_document(&annotated_document_to_dict(&adoc));
+        assert_eq!(adoc_back.chunk_failures.len(), 1);
+        let failure = &adoc_back.chunk_failures[0];
+        assert_eq!(failure.error_kind, ChunkErrorKind::Parse);
+        assert_eq!(failure.char_interval.end_pos, Some(5));
+        assert_eq!(failure.raw_output.as_deref(), Some("```yaml\n["));
+    }
+
+    #[test]
+    fn test_chunk_diagnostics_round_trip() {
+        let mut adoc = AnnotatedDocument::new(Some("doc_1".to_string()), Some(vec![]), Some("hello".to_string()));
+        let mut diagnostics = ChunkDiagnostics::new(CharInterval::new(Some(0), Some(5)), 1);
+        diagnostics.repair_attempts.push(RepairAttempt {
+            output: "extractions: [".to_string(),
+            error: "unexpected end".to_string(),
+        });
+        diagnostics.repaired_output = Some("extractions: []".to_string());
+        diagnostics.parse_repairs.push("removed trailing commas".to_string());
+        diagnostics.reasoning = Some("Look for people.".to_string());
+        diagnostics
+            .split_failures
+            .push("truncated failure for chunk [0, 5): cut off".to_string());
+        adoc.chunk_diagnostics.push(diagnostics.clone());
+
+        let adoc_back = dict_to_annotated_document(&annotated_document_to_dict(&adoc));
+        assert_eq!(adoc_back.chunk_diagnostics, vec![diagnostics]);
+    }
+
+    #[test]
+    fn test_skipped_chunks_round_trip() {
+        let mut adoc = AnnotatedDocument::new(Some("doc_1".to_string()), Some(vec![]), Some("hello".to_string()));
+        adoc.skipped_chunks.push(CharInterval::new(Some(0), Some(5)));
+
+        let adoc_back = dict_to_annotated_document(&annotated_document_to_dict(&adoc));
+        assert_eq!(adoc_back.skipped_chunks, vec![CharInterval::new(Some(0), Some(5))]);
+    }
+
+    #[test]
+    fn test_verification_error_round_trip() {
+        let mut adoc = AnnotatedDocument::new(Some("doc_1".to_string()), Some(vec![]), Some("hello".to_string()));
+        adoc.verification_error = Some("No scored outputs from verifier.".to_string());
+
+        let adoc_back = dict_to_annotated_document(&annotated_document_to_dict(&adoc));
+        assert_eq!(adoc_back.verification_error, adoc.verification_error);
     }
 
     #[test]
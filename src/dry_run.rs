@@ -0,0 +1,187 @@
+/*!
+Dry runs: what an annotation run would send, without sending it.
+
+`Annotator::dry_run` chunks the documents and renders every prompt the run
+would send, with no network calls. The resulting `DryRunReport` can write the
+prompts to JSONL for inspection and project the cost of the run for a set of
+models.
+
+Token counts are estimates of about four characters per token, which is close
+for English text with most tokenizers; the output of each prompt is assumed to
+be about as long as its chunk, since extractions quote the source text.
+
+Usage example:
+    let report = annotator.dry_run(documents, 1000, 2)?;
+    report.write_jsonl(Path::new("prompts.jsonl"))?;
+    report.print_budget(&[ModelPricing::new("deepseek-chat", 0.27, 1.10)]);
+*/
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::data::CharInterval;
+
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimates the number of tokens in `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// One prompt a run would send.
+#[derive(Debug, Clone)]
+pub struct DryRunPrompt {
+    pub document_id: String,
+    pub pass_index: usize,
+    /// Index of the self-consistency sample; 0 without self-consistency.
+    pub sample_index: usize,
+    pub char_interval: CharInterval,
+    pub prompt: String,
+    pub estimated_prompt_tokens: usize,
+    pub estimated_output_tokens: usize,
+}
+
+/// Price of a model in currency units per million tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelPricing {
+    pub model_id: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    pub fn new(model_id: impl Into<String>, input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            model_id: model_id.into(),
+            input_per_million,
+            output_per_million,
+        }
+    }
+}
+
+/// The chunks and prompts of a run that was not sent.
+#[derive(Debug, Clone, Default)]
+pub struct DryRunReport {
+    pub num_documents: usize,
+    pub num_chunks: usize,
+    pub prompts: Vec<DryRunPrompt>,
+}
+
+impl DryRunReport {
+    pub fn estimated_prompt_tokens(&self) -> usize {
+        self.prompts.iter().map(|p| p.estimated_prompt_tokens).sum()
+    }
+
+    pub fn estimated_output_tokens(&self) -> usize {
+        self.prompts.iter().map(|p| p.estimated_output_tokens).sum()
+    }
+
+    /// Projected cost of the run on a model.
+    pub fn estimated_cost(&self, pricing: &ModelPricing) -> f64 {
+        (self.estimated_prompt_tokens() as f64 * pricing.input_per_million
+            + self.estimated_output_tokens() as f64 * pricing.output_per_million)
+            / 1_000_000.0
+    }
+
+    /// Writes one JSON object per prompt to `path`.
+    pub fn write_jsonl(&self, path: &Path) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for prompt in &self.prompts {
+            let line = json!({
+                "document_id": prompt.document_id,
+                "pass_index": prompt.pass_index,
+                "sample_index": prompt.sample_index,
+                "char_interval": {
+                    "start_pos": prompt.char_interval.start_pos,
+                    "end_pos": prompt.char_interval.end_pos,
+                },
+                "prompt": prompt.prompt,
+                "estimated_prompt_tokens": prompt.estimated_prompt_tokens,
+                "estimated_output_tokens": prompt.estimated_output_tokens,
+            });
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()
+    }
+
+    /// Renders the budget report: chunk and prompt counts, estimated tokens
+    /// and the projected cost on each model.
+    pub fn budget_report(&self, pricing: &[ModelPricing]) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "Documents: {}", self.num_documents);
+        let _ = writeln!(report, "Chunks: {}", self.num_chunks);
+        let _ = writeln!(report, "Prompts: {}", self.prompts.len());
+        let _ = writeln!(report, "Estimated prompt tokens: {}", self.estimated_prompt_tokens());
+        let _ = writeln!(report, "Estimated output tokens: {}", self.estimated_output_tokens());
+        for model in pricing {
+            let _ = writeln!(
+                report,
+                "Estimated cost on {}: {:.4}",
+                model.model_id,
+                self.estimated_cost(model)
+            );
+        }
+        report
+    }
+
+    /// Prints [`DryRunReport::budget_report`].
+    pub fn print_budget(&self, pricing: &[ModelPricing]) {
+        print!("{}", self.budget_report(pricing));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> DryRunReport {
+        let prompt = |pass_index, text: &str| DryRunPrompt {
+            document_id: "doc_1".to_string(),
+            pass_index,
+            sample_index: 0,
+            char_interval: CharInterval::new(Some(0), Some(8)),
+            prompt: text.to_string(),
+            estimated_prompt_tokens: estimate_tokens(text),
+            estimated_output_tokens: 2,
+        };
+        DryRunReport {
+            num_documents: 1,
+            num_chunks: 1,
+            prompts: vec![prompt(0, "0123456789"), prompt(1, "01234567")],
+        }
+    }
+
+    #[test]
+    fn test_budget_report() {
+        let report = report();
+        assert_eq!(report.estimated_prompt_tokens(), 5);
+        assert_eq!(report.estimated_output_tokens(), 4);
+
+        let pricing = ModelPricing::new("model-a", 1_000_000.0, 500_000.0);
+        assert!((report.estimated_cost(&pricing) - 7.0).abs() < 1e-9);
+        let budget = report.budget_report(&[pricing]);
+        assert!(budget.contains("Prompts: 2"));
+        assert!(budget.contains("Estimated cost on model-a: 7.0000"));
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prompts.jsonl");
+        report().write_jsonl(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["pass_index"], 1);
+        assert_eq!(lines[1]["char_interval"]["end_pos"], 8);
+        assert_eq!(lines[0]["prompt"], "0123456789");
+    }
+}
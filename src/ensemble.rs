@@ -0,0 +1,176 @@
+/*!
+Extraction with an ensemble of language models.
+
+An `EnsembleModel` sends every prompt to several `BaseLanguageModel` backends,
+for example DeepSeek and a local model, and returns one scored output per
+member tagged with the member's id. With `Annotator::with_ensemble_agreement`,
+the annotator resolves every member's output and keeps the extractions the
+members agree on, recording in `Extraction::model_ids` which models produced
+each one.
+
+Usage example:
+    let ensemble = EnsembleModel::new()
+        .with_member("deepseek-chat", deepseek_model)
+        .with_member("gpt-4o-mini", openai_model);
+    let annotator = Annotator::new(ensemble, prompt_template, format_type, None, true)
+        .with_ensemble_agreement(Agreement::Majority);
+*/
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+
+use crate::inference::{BaseLanguageModel, InferenceOutputError, ScoredOutput};
+
+/// How many ensemble members must produce an extraction for it to be kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Agreement {
+    /// Keep what any member found.
+    Any,
+    /// Keep what more than half of the members found.
+    #[default]
+    Majority,
+    /// Keep only what every member found.
+    All,
+    /// Keep what at least this many members found.
+    AtLeast(usize),
+}
+
+impl Agreement {
+    /// The number of votes required out of `members`.
+    pub fn min_votes(&self, members: usize) -> usize {
+        match self {
+            Agreement::Any => 1,
+            Agreement::Majority => members / 2 + 1,
+            Agreement::All => members.max(1),
+            Agreement::AtLeast(votes) => *votes,
+        }
+    }
+}
+
+/// Runs each prompt through every member model.
+///
+/// For every prompt the outputs are the top output of each member, in member
+/// order, with `model_id` set to the member id. A member that fails yields an
+/// empty output so the others still count; inference only fails when every
+/// member does.
+#[derive(Default)]
+pub struct EnsembleModel {
+    members: Vec<(String, Box<dyn BaseLanguageModel>)>,
+}
+
+impl EnsembleModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a member model identified by `model_id`.
+    pub fn with_member(mut self, model_id: impl Into<String>, model: impl BaseLanguageModel + 'static) -> Self {
+        self.members.push((model_id.into(), Box::new(model)));
+        self
+    }
+
+    /// The ids of the members, in order.
+    pub fn model_ids(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(|(model_id, _)| model_id.as_str())
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for EnsembleModel {
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+        if self.members.is_empty() {
+            return Err(InferenceOutputError::new("Ensemble has no member models."));
+        }
+        let member_outputs = join_all(
+            self.members
+                .iter()
+                .map(|(_, model)| model.infer(batch_prompts, kwargs.clone())),
+        )
+        .await;
+
+        let mut errors = Vec::new();
+        let mut outputs = vec![Vec::with_capacity(self.members.len()); batch_prompts.len()];
+        for ((model_id, _), result) in self.members.iter().zip(member_outputs) {
+            let mut member_outputs = match result {
+                Ok(member_outputs) => member_outputs.into_iter(),
+                Err(e) => {
+                    errors.push(format!("{}: {}", model_id, e.message));
+                    Vec::new().into_iter()
+                }
+            };
+            for prompt_outputs in outputs.iter_mut() {
+                let top = member_outputs
+                    .next()
+                    .and_then(|scored| scored.into_iter().next())
+                    .unwrap_or_else(|| ScoredOutput::new(None, None));
+                prompt_outputs.push(top.with_model_id(model_id.as_str()));
+            }
+        }
+        if errors.len() == self.members.len() {
+            return Err(InferenceOutputError::new(format!(
+                "All ensemble members failed: {}",
+                errors.join("; ")
+            )));
+        }
+        Ok(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedModel(Option<&'static str>);
+
+    #[async_trait]
+    impl BaseLanguageModel for FixedModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: Option<HashMap<String, serde_json::Value>>,
+        ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+            let output = self.0.ok_or_else(|| InferenceOutputError::new("unavailable"))?;
+            Ok(batch_prompts
+                .iter()
+                .map(|_| vec![ScoredOutput::new(Some(1.0), Some(output.to_string()))])
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_tags_member_outputs() {
+        let ensemble = EnsembleModel::new()
+            .with_member("a", FixedModel(Some("first")))
+            .with_member("b", FixedModel(None))
+            .with_member("c", FixedModel(Some("third")));
+        let outputs = ensemble
+            .infer(&["p1".to_string(), "p2".to_string()], None)
+            .await
+            .unwrap();
+
+        assert_eq!(outputs.len(), 2);
+        let summary: Vec<_> = outputs[1]
+            .iter()
+            .map(|o| (o.model_id.as_deref().unwrap(), o.output.as_deref()))
+            .collect();
+        assert_eq!(summary, vec![("a", Some("first")), ("b", None), ("c", Some("third"))]);
+
+        let failing = EnsembleModel::new().with_member("b", FixedModel(None));
+        assert!(failing.infer(&["p".to_string()], None).await.is_err());
+    }
+
+    #[test]
+    fn test_agreement_min_votes() {
+        assert_eq!(Agreement::Any.min_votes(3), 1);
+        assert_eq!(Agreement::Majority.min_votes(3), 2);
+        assert_eq!(Agreement::Majority.min_votes(4), 3);
+        assert_eq!(Agreement::All.min_votes(3), 3);
+        assert_eq!(Agreement::AtLeast(2).min_votes(5), 2);
+    }
+}
@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 
+#[allow(dead_code)]
 const OLLAMA_DEFAULT_MODEL_URL: &str = "http://localhost:11434";
 
 /// Scored output from language model inference.
@@ -14,11 +15,78 @@ const OLLAMA_DEFAULT_MODEL_URL: &str = "http://localhost:11434";
 pub struct ScoredOutput {
     pub score: Option<f64>,
     pub output: Option<String>,
+    /// The model that produced the output, when the backend reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    /// Why the model stopped generating, as reported by the provider (for
+    /// example "stop" or "length").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    /// The reasoning of a reasoning model, kept apart from `output`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
 }
 
 impl ScoredOutput {
     pub fn new(score: Option<f64>, output: Option<String>) -> Self {
-        Self { score, output }
+        Self {
+            score,
+            output,
+            model_id: None,
+            finish_reason: None,
+            reasoning: None,
+        }
+    }
+
+    pub fn with_model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = Some(model_id.into());
+        self
+    }
+
+    pub fn with_finish_reason(mut self, finish_reason: impl Into<String>) -> Self {
+        self.finish_reason = Some(finish_reason.into());
+        self
+    }
+
+    pub fn with_reasoning(mut self, reasoning: impl Into<String>) -> Self {
+        self.reasoning = Some(reasoning.into());
+        self
+    }
+
+    /// Builds the output of an OpenAI-compatible chat completion response.
+    ///
+    /// The reasoning comes from the `reasoning_content` field of the message,
+    /// as DeepSeek-R1 returns it, and from `<think>` blocks in the content.
+    fn from_chat_completion(response_json: &serde_json::Value, model_id: &str) -> Self {
+        let choice = &response_json["choices"][0];
+        let mut reasoning: Vec<String> = choice["message"]["reasoning_content"]
+            .as_str()
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| r.trim().to_string())
+            .into_iter()
+            .collect();
+        let output_text = choice["message"]["content"].as_str().map(|content| {
+            let (think, answer) = split_reasoning(content);
+            reasoning.extend(think);
+            answer
+        });
+
+        let mut scored_output = ScoredOutput::new(Some(1.0), output_text).with_model_id(model_id);
+        if let Some(finish_reason) = choice["finish_reason"].as_str() {
+            scored_output = scored_output.with_finish_reason(finish_reason);
+        }
+        if !reasoning.is_empty() {
+            scored_output = scored_output.with_reasoning(reasoning.join("\n\n"));
+        }
+        scored_output
+    }
+
+    /// Whether the provider reported that the output hit the output token
+    /// limit.
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason
+            .as_deref()
+            .is_some_and(|reason| reason.eq_ignore_ascii_case("length") || reason.eq_ignore_ascii_case("max_tokens"))
     }
 }
 
@@ -35,6 +103,44 @@ impl std::fmt::Display for ScoredOutput {
     }
 }
 
+/// Splits the `<think>...</think>` reasoning of a reasoning model from its
+/// answer, returning the reasoning, if any, and the answer.
+///
+/// Reasoning cut off before its closing tag leaves no answer, and a closing
+/// tag without an opening one, which some models emit, ends reasoning that
+/// started at the beginning of the text.
+pub fn split_reasoning(text: &str) -> (Option<String>, String) {
+    const OPEN: &str = "<think>";
+    const CLOSE: &str = "</think>";
+    let mut reasoning = Vec::new();
+    let mut answer = String::new();
+    let mut rest = text;
+    if let (Some(close), open) = (rest.find(CLOSE), rest.find(OPEN))
+        && open.is_none_or(|open| close < open)
+    {
+        reasoning.push(rest[..close].trim());
+        rest = &rest[close + CLOSE.len()..];
+    }
+    while let Some(open) = rest.find(OPEN) {
+        answer.push_str(&rest[..open]);
+        let inner = &rest[open + OPEN.len()..];
+        match inner.find(CLOSE) {
+            Some(close) => {
+                reasoning.push(inner[..close].trim());
+                rest = &inner[close + CLOSE.len()..];
+            }
+            None => {
+                reasoning.push(inner.trim());
+                rest = "";
+            }
+        }
+    }
+    answer.push_str(rest);
+    reasoning.retain(|r| !r.is_empty());
+    let reasoning = (!reasoning.is_empty()).then(|| reasoning.join("\n\n"));
+    (reasoning, answer.trim().to_string())
+}
+
 /// Exception raised when no scored outputs are available from the language model.
 #[derive(Error, Debug)]
 #[error("Inference output error: {message}")]
@@ -82,14 +188,17 @@ pub struct OpenAILanguageModel {
     model_id: String,
     api_key: String,
     base_url: Option<String>,
+    #[allow(dead_code)]
     organization: Option<String>,
     format_type: FormatType,
     temperature: f64,
     max_workers: usize,
+    #[allow(dead_code)]
     extra_kwargs: HashMap<String, serde_json::Value>,
 }
 
 impl OpenAILanguageModel {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         model_id: Option<String>,
         api_key: String,
@@ -166,11 +275,7 @@ impl OpenAILanguageModel {
             .json()
             .await
             .map_err(|e| InferenceOutputError::new(e.to_string()))?;
-        let output_text = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .map(|s| s.to_string());
-
-        Ok(ScoredOutput::new(Some(1.0), output_text))
+        Ok(ScoredOutput::from_chat_completion(&response_json, &self.model_id))
     }
 
     pub fn parse_output(&self, output: &str) -> std::result::Result<serde_json::Value, InferenceOutputError> {
@@ -227,6 +332,7 @@ pub struct DeepSeekLanguageModel {
     format_type: FormatType,
     temperature: f64,
     max_workers: usize,
+    #[allow(dead_code)]
     extra_kwargs: HashMap<String, serde_json::Value>,
 }
 
@@ -305,11 +411,7 @@ impl DeepSeekLanguageModel {
             .json()
             .await
             .map_err(|e| InferenceOutputError::new(e.to_string()))?;
-        let output_text = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .map(|s| s.to_string());
-
-        Ok(ScoredOutput::new(Some(1.0), output_text))
+        Ok(ScoredOutput::from_chat_completion(&response_json, &self.model_id))
     }
 
     pub fn parse_output(&self, output: &str) -> std::result::Result<serde_json::Value, InferenceOutputError> {
@@ -376,6 +478,51 @@ mod tests {
         assert!(display_str.contains("test output"));
     }
 
+    #[test]
+    fn test_scored_output_truncation() {
+        let output = ScoredOutput::new(Some(1.0), Some("[".to_string()));
+        assert!(!output.is_truncated());
+        assert!(output.clone().with_finish_reason("length").is_truncated());
+        assert!(output.clone().with_finish_reason("MAX_TOKENS").is_truncated());
+        assert!(!output.with_finish_reason("stop").is_truncated());
+    }
+
+    #[test]
+    fn test_split_reasoning() {
+        let (reasoning, answer) =
+            split_reasoning("<think>\nFind people.\n```yaml\nx\n```\n</think>\n```yaml\nextractions: []\n```");
+        assert_eq!(reasoning.as_deref(), Some("Find people.\n```yaml\nx\n```"));
+        assert_eq!(answer, "```yaml\nextractions: []\n```");
+
+        let (reasoning, answer) = split_reasoning("Find people.</think>\nextractions: []");
+        assert_eq!(reasoning.as_deref(), Some("Find people."));
+        assert_eq!(answer, "extractions: []");
+
+        let (reasoning, answer) = split_reasoning("<think>Still thinking");
+        assert_eq!(reasoning.as_deref(), Some("Still thinking"));
+        assert!(answer.is_empty());
+
+        assert_eq!(
+            split_reasoning("extractions: []"),
+            (None, "extractions: []".to_string())
+        );
+    }
+
+    #[test]
+    fn test_scored_output_from_chat_completion() {
+        let response = serde_json::json!({
+            "choices": [{
+                "message": {"content": "<think>b</think>answer", "reasoning_content": "a"},
+                "finish_reason": "stop",
+            }]
+        });
+        let output = ScoredOutput::from_chat_completion(&response, "deepseek-reasoner");
+        assert_eq!(output.output.as_deref(), Some("answer"));
+        assert_eq!(output.reasoning.as_deref(), Some("a\n\nb"));
+        assert_eq!(output.model_id.as_deref(), Some("deepseek-reasoner"));
+        assert_eq!(output.finish_reason.as_deref(), Some("stop"));
+    }
+
     #[test]
     fn test_openai_model_creation() {
         let model = OpenAILanguageModel::new(None, "test-api-key".to_string(), None, None, None, None, None, None);
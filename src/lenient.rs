@@ -0,0 +1,447 @@
+/*!
+Tolerant parsing of model output.
+
+Models often wrap their answer in prose, emit several fenced blocks, or write
+JSON with trailing commas, single quotes and comments; output cut off at the
+token limit ends in the middle of an item with its brackets unclosed. The
+strict parsers reject all of it. The functions here repair the common faults
+and salvage the complete items of truncated output, reporting every repair
+they applied as a `ParseRepair`.
+
+`Resolver::with_lenient_parsing` falls back to this layer when strict parsing
+fails.
+
+Usage example:
+    let (content, mut repairs) = lenient::select_content(output, true, false);
+    let (value, more_repairs) = lenient::parse_json(&content)?;
+    repairs.extend(more_repairs);
+*/
+
+use std::fmt;
+
+use serde_json::Value as JsonValue;
+
+use crate::resolver::schema::EXTRACTIONS_KEY;
+
+/// A repair applied to model output so that it would parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRepair {
+    /// Picked one of several fenced blocks (zero-based `index` of `count`).
+    SelectedFencedBlock { index: usize, count: usize },
+    /// The opening fence of the content had no closing fence.
+    UnterminatedFence,
+    /// Dropped prose before or after the content.
+    StrippedProse,
+    /// Removed `//`, `/* */` or `#` comments.
+    RemovedComments,
+    /// Turned single-quoted strings into double-quoted ones.
+    ConvertedSingleQuotes,
+    /// Removed commas before closing brackets.
+    RemovedTrailingCommas,
+    /// Dropped the last, incomplete item of truncated output.
+    DroppedIncompleteItem,
+    /// Closed brackets left open by truncated output.
+    ClosedBrackets(usize),
+}
+
+impl ParseRepair {
+    /// Whether the repair means the output was cut off.
+    pub fn is_truncation(&self) -> bool {
+        matches!(
+            self,
+            ParseRepair::DroppedIncompleteItem | ParseRepair::ClosedBrackets(_)
+        )
+    }
+}
+
+impl fmt::Display for ParseRepair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRepair::SelectedFencedBlock { index, count } => {
+                write!(f, "selected fenced block {} of {}", index + 1, count)
+            }
+            ParseRepair::UnterminatedFence => write!(f, "read unterminated code fence"),
+            ParseRepair::StrippedProse => write!(f, "stripped surrounding prose"),
+            ParseRepair::RemovedComments => write!(f, "removed comments"),
+            ParseRepair::ConvertedSingleQuotes => write!(f, "converted single quotes"),
+            ParseRepair::RemovedTrailingCommas => write!(f, "removed trailing commas"),
+            ParseRepair::DroppedIncompleteItem => write!(f, "dropped incomplete item"),
+            ParseRepair::ClosedBrackets(count) => write!(f, "closed {} unclosed brackets", count),
+        }
+    }
+}
+
+struct FencedBlock<'a> {
+    language: &'a str,
+    content: &'a str,
+    terminated: bool,
+}
+
+fn fenced_blocks(input: &str) -> Vec<FencedBlock<'_>> {
+    let mut blocks = Vec::new();
+    let mut rest = input;
+    while let Some(open) = rest.find("```") {
+        let after_open = &rest[open + 3..];
+        let (language, body) = match after_open.find('\n') {
+            Some(newline) => (after_open[..newline].trim(), &after_open[newline + 1..]),
+            None => (after_open.trim(), ""),
+        };
+        match body.find("```") {
+            Some(close) => {
+                blocks.push(FencedBlock {
+                    language,
+                    content: body[..close].trim(),
+                    terminated: true,
+                });
+                rest = &body[close + 3..];
+            }
+            None => {
+                blocks.push(FencedBlock {
+                    language,
+                    content: body.trim(),
+                    terminated: false,
+                });
+                break;
+            }
+        }
+    }
+    blocks
+}
+
+fn is_language(tag: &str, format_is_yaml: bool) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    if format_is_yaml {
+        tag == "yaml" || tag == "yml"
+    } else {
+        tag == "json"
+    }
+}
+
+/// Picks the content to parse out of `input`.
+///
+/// With `fence_output`, the block tagged with the expected language is
+/// preferred over untagged ones, and among several candidates the first one
+/// mentioning the extractions key. Without fences, or when the output has
+/// none, prose before the content is dropped.
+pub fn select_content(input: &str, fence_output: bool, format_is_yaml: bool) -> (String, Vec<ParseRepair>) {
+    let mut repairs = Vec::new();
+    let blocks = if fence_output { fenced_blocks(input) } else { Vec::new() };
+    if blocks.is_empty() {
+        let content = strip_leading_prose(input, format_is_yaml);
+        if content.len() != input.trim().len() {
+            repairs.push(ParseRepair::StrippedProse);
+        }
+        return (content.to_string(), repairs);
+    }
+
+    let tagged: Vec<usize> = (0..blocks.len())
+        .filter(|&i| is_language(blocks[i].language, format_is_yaml))
+        .collect();
+    let untagged: Vec<usize> = (0..blocks.len()).filter(|&i| blocks[i].language.is_empty()).collect();
+    let candidates = if !tagged.is_empty() {
+        tagged
+    } else if !untagged.is_empty() {
+        untagged
+    } else {
+        (0..blocks.len()).collect()
+    };
+    let index = candidates
+        .iter()
+        .copied()
+        .find(|&i| blocks[i].content.contains(EXTRACTIONS_KEY))
+        .unwrap_or(candidates[0]);
+
+    if blocks.len() > 1 {
+        repairs.push(ParseRepair::SelectedFencedBlock {
+            index,
+            count: blocks.len(),
+        });
+    }
+    if !blocks[index].terminated {
+        repairs.push(ParseRepair::UnterminatedFence);
+    }
+    (blocks[index].content.to_string(), repairs)
+}
+
+/// Drops text before the first line that can start the content.
+fn strip_leading_prose(input: &str, format_is_yaml: bool) -> &str {
+    let input = input.trim();
+    if format_is_yaml {
+        let mut offset = 0;
+        for line in input.split_inclusive('\n') {
+            if line.starts_with(EXTRACTIONS_KEY) || line.starts_with("- ") {
+                return &input[offset..];
+            }
+            offset += line.len();
+        }
+        input
+    } else {
+        input.find(['{', '[']).map_or(input, |start| &input[start..])
+    }
+}
+
+/// Parses JSON after repairing common syntax faults, salvaging the complete
+/// items of truncated arrays.
+pub fn parse_json(content: &str) -> Result<(JsonValue, Vec<ParseRepair>), serde_json::Error> {
+    let (repaired, repairs) = repair_json(content);
+    serde_json::from_str(&repaired).map(|value| (value, repairs))
+}
+
+/// Rewrites JSON-like text into JSON, reporting what it changed.
+///
+/// Strings, comments and brackets are tracked in a single scan. When the text
+/// ends inside an array, everything after its last complete element, or
+/// after its opening bracket when it has none, is dropped and the open
+/// brackets are closed.
+fn repair_json(content: &str) -> (String, Vec<ParseRepair>) {
+    fn note(repairs: &mut Vec<ParseRepair>, repair: ParseRepair) {
+        if !repairs.contains(&repair) {
+            repairs.push(repair);
+        }
+    }
+    let mut repairs = Vec::new();
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    let mut stack: Vec<char> = Vec::new();
+    // Output length and open brackets after the last complete array element.
+    let mut checkpoint: Option<(usize, Vec<char>)> = None;
+    let mut string_quote: Option<char> = None;
+    let mut started = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(quote) = string_quote {
+            match c {
+                '\\' if quote == '\'' && chars.get(i + 1) == Some(&'\'') => {
+                    out.push('\'');
+                    i += 1;
+                }
+                '\\' => {
+                    out.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        out.push(next);
+                        i += 1;
+                    }
+                }
+                '"' if quote == '\'' => out.push_str("\\\""),
+                _ if c == quote => {
+                    out.push('"');
+                    string_quote = None;
+                }
+                _ => out.push(c),
+            }
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => {
+                if c == '\'' {
+                    note(&mut repairs, ParseRepair::ConvertedSingleQuotes);
+                }
+                out.push('"');
+                string_quote = Some(c);
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                note(&mut repairs, ParseRepair::RemovedComments);
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+                note(&mut repairs, ParseRepair::RemovedComments);
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                note(&mut repairs, ParseRepair::RemovedComments);
+                continue;
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, Some('}') | Some(']')) {
+                    note(&mut repairs, ParseRepair::RemovedTrailingCommas);
+                } else {
+                    if stack.last() == Some(&'[') {
+                        checkpoint = Some((out.len(), stack.clone()));
+                    }
+                    out.push(c);
+                }
+            }
+            '{' | '[' => {
+                stack.push(c);
+                started = true;
+                out.push(c);
+                // An array cut off inside its first element is salvaged empty.
+                if c == '[' {
+                    checkpoint = Some((out.len(), stack.clone()));
+                }
+            }
+            '}' | ']' => {
+                stack.pop();
+                out.push(c);
+                if stack.last() == Some(&'[') {
+                    checkpoint = Some((out.len(), stack.clone()));
+                }
+                if started && stack.is_empty() {
+                    if chars[i + 1..].iter().any(|c| !c.is_whitespace()) {
+                        note(&mut repairs, ParseRepair::StrippedProse);
+                    }
+                    return (out, repairs);
+                }
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+
+    if string_quote.is_none() && stack.is_empty() {
+        return (out, repairs);
+    }
+    if let Some((len, open)) = checkpoint {
+        if !out[len..].trim().is_empty() {
+            repairs.push(ParseRepair::DroppedIncompleteItem);
+        }
+        out.truncate(len);
+        stack = open;
+    } else if string_quote.is_some() {
+        out.push('"');
+    }
+    if !stack.is_empty() {
+        repairs.push(ParseRepair::ClosedBrackets(stack.len()));
+    }
+    for open in stack.iter().rev() {
+        out.push(if *open == '{' { '}' } else { ']' });
+    }
+    (out, repairs)
+}
+
+/// Parses YAML, dropping the last list item when the output was cut off or
+/// when the text only parses without it.
+pub fn parse_yaml(content: &str, truncated: bool) -> Result<(JsonValue, Vec<ParseRepair>), serde_yaml::Error> {
+    let error = match serde_yaml::from_str(content) {
+        Ok(value) if !truncated => return Ok((value, Vec::new())),
+        Ok(_) => None,
+        Err(e) => Some(e),
+    };
+    match drop_last_item(content) {
+        Some(shortened) => match serde_yaml::from_str(shortened) {
+            Ok(value) => Ok((value, vec![ParseRepair::DroppedIncompleteItem])),
+            Err(e) => Err(error.unwrap_or(e)),
+        },
+        None => match error {
+            Some(e) => Err(e),
+            None => serde_yaml::from_str(content).map(|value| (value, Vec::new())),
+        },
+    }
+}
+
+/// Returns `content` without its last list item at the indentation of the
+/// first one.
+fn drop_last_item(content: &str) -> Option<&str> {
+    let item_indent = content.lines().find_map(|line| {
+        let trimmed = line.trim_start();
+        trimmed.starts_with("- ").then(|| line.len() - trimmed.len())
+    })?;
+    let mut offset = 0;
+    let mut last_item = None;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("- ") && line.len() - trimmed.len() == item_indent {
+            last_item = Some(offset);
+        }
+        offset += line.len();
+    }
+    last_item.map(|start| &content[..start])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_json_syntax_faults() {
+        let content =
+            "{\n  // people\n  'extractions': [\n    {\"person\": 'O\\'Brien', \"note\": 'say \"hi\"',},\n  ],\n}";
+        let (value, repairs) = parse_json(content).unwrap();
+        assert_eq!(value["extractions"][0]["person"], "O'Brien");
+        assert_eq!(value["extractions"][0]["note"], "say \"hi\"");
+        assert_eq!(
+            repairs,
+            vec![
+                ParseRepair::RemovedComments,
+                ParseRepair::ConvertedSingleQuotes,
+                ParseRepair::RemovedTrailingCommas,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_salvage_truncated_json() {
+        let content = r#"{"extractions": [{"person": "Alice"}, {"person": "Bob", "person_attr"#;
+        let (value, repairs) = parse_json(content).unwrap();
+        assert_eq!(value["extractions"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            repairs,
+            vec![ParseRepair::DroppedIncompleteItem, ParseRepair::ClosedBrackets(2)]
+        );
+
+        let (value, _) = parse_json(r#"["Alice", "Bo"#).unwrap();
+        assert_eq!(value, serde_json::json!(["Alice"]));
+    }
+
+    #[test]
+    fn test_salvage_json_truncated_in_first_item() {
+        let (value, repairs) = parse_json(r#"{"extractions": [{"person": "Al"#).unwrap();
+        assert_eq!(value, serde_json::json!({"extractions": []}));
+        assert_eq!(
+            repairs,
+            vec![ParseRepair::DroppedIncompleteItem, ParseRepair::ClosedBrackets(2)]
+        );
+
+        let (value, _) = parse_json(r#"{"extractions": [{"person"#).unwrap();
+        assert_eq!(value, serde_json::json!({"extractions": []}));
+        let (value, _) = parse_json(r#"["Al"#).unwrap();
+        assert_eq!(value, serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_select_content() {
+        let output =
+            "Here is an example:\n```json\n{\"a\": 1}\n```\nAnd the answer:\n```json\n{\"extractions\": []}\n```";
+        let (content, repairs) = select_content(output, true, false);
+        assert_eq!(content, "{\"extractions\": []}");
+        assert_eq!(repairs, vec![ParseRepair::SelectedFencedBlock { index: 1, count: 2 }]);
+
+        let (content, repairs) = select_content("Sure!\n```\nextractions:\n  - person: Al", true, true);
+        assert_eq!(content, "extractions:\n  - person: Al");
+        assert_eq!(repairs, vec![ParseRepair::UnterminatedFence]);
+
+        let (content, repairs) = select_content("The result is {\"extractions\": []} as asked.", false, false);
+        assert_eq!(content, "{\"extractions\": []} as asked.");
+        assert_eq!(repairs, vec![ParseRepair::StrippedProse]);
+        let (_, repairs) = parse_json(&content).unwrap();
+        assert_eq!(repairs, vec![ParseRepair::StrippedProse]);
+    }
+
+    #[test]
+    fn test_parse_truncated_yaml() {
+        let content = "extractions:\n  - person: Alice\n    person_index: 1\n  - person: Bo";
+        let (value, repairs) = parse_yaml(content, true).unwrap();
+        assert_eq!(value["extractions"].as_array().unwrap().len(), 1);
+        assert_eq!(repairs, vec![ParseRepair::DroppedIncompleteItem]);
+
+        let (value, repairs) = parse_yaml(content, false).unwrap();
+        assert_eq!(value["extractions"].as_array().unwrap().len(), 2);
+        assert!(repairs.is_empty());
+    }
+}
@@ -1,13 +1,22 @@
 pub mod annotation;
+pub mod checkpoint;
 pub mod chunking;
+pub mod consolidation;
 pub mod data;
 pub mod data_lib;
+pub mod dry_run;
+pub mod ensemble;
 pub mod error;
 pub mod inference;
 pub mod io;
+pub mod lenient;
+pub mod merging;
+pub mod normalization;
+pub mod prefilter;
 pub mod progress;
 pub mod prompting;
 pub mod resolver;
 pub mod schema;
 pub mod tokenizer;
+pub mod verification;
 pub mod visualization;
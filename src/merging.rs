@@ -0,0 +1,349 @@
+/*!
+Strategies for merging the extractions of several extraction passes.
+
+Each pass over a document produces its own list of extractions. A
+`MergeStrategy` combines them into the final list of the annotated document.
+All built-in strategies return extractions ordered by their position in the
+document, with unaligned extractions last, so the output does not depend on
+which pass found an extraction first.
+
+Usage example:
+    let annotator = Annotator::new(language_model, prompt_template, format_type, None, true)
+        .with_merge_strategy(MajorityVote::default());
+*/
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::annotation::{extractions_overlap, merge_non_overlapping_extractions};
+use crate::data::Extraction;
+
+/// Combines the extractions of all passes over one document.
+pub trait MergeStrategy: Send + Sync {
+    /// Merges `pass_extractions`, which holds one list per pass in pass order.
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction>;
+}
+
+/// Keeps every extraction of the first pass and adds later extractions only
+/// where they do not overlap anything kept so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FirstWins;
+
+impl MergeStrategy for FirstWins {
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction> {
+        sorted_by_position(merge_non_overlapping_extractions(pass_extractions))
+    }
+}
+
+/// Keeps the extractions of all passes, dropping exact duplicates (same
+/// class and span, or same class and text when unaligned).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Union;
+
+impl MergeStrategy for Union {
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction> {
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+        for extraction in pass_extractions.iter().flatten() {
+            if seen.insert(SpanKey::of(extraction)) {
+                merged.push(extraction.clone());
+            }
+        }
+        sorted_by_position(merged)
+    }
+}
+
+/// Keeps extractions found by enough passes, setting their confidence to the
+/// fraction of passes that agree.
+///
+/// Two extractions agree when they have the same class and span (or the same
+/// class and text when unaligned). Without `min_votes`, a strict majority of
+/// the passes is required.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MajorityVote {
+    pub min_votes: Option<usize>,
+}
+
+impl MajorityVote {
+    pub fn with_min_votes(min_votes: usize) -> Self {
+        Self {
+            min_votes: Some(min_votes),
+        }
+    }
+}
+
+impl MergeStrategy for MajorityVote {
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction> {
+        let min_votes = self.min_votes.unwrap_or(pass_extractions.len() / 2 + 1);
+        vote(pass_extractions, min_votes)
+    }
+}
+
+/// Resolves overlaps in favour of the longest span; ties go to the earlier
+/// pass.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestSpan;
+
+impl MergeStrategy for LongestSpan {
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction> {
+        select_non_overlapping(pass_extractions, |a, b| span_len(b).cmp(&span_len(a)))
+    }
+}
+
+/// Resolves overlaps in favour of the extraction with the highest confidence;
+/// extractions without a confidence rank last and ties go to the earlier pass.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighestConfidence;
+
+impl MergeStrategy for HighestConfidence {
+    fn merge(&self, pass_extractions: &[Vec<Extraction>]) -> Vec<Extraction> {
+        select_non_overlapping(pass_extractions, |a, b| {
+            let confidence = |e: &Extraction| e.confidence.unwrap_or(f64::NEG_INFINITY);
+            confidence(b).total_cmp(&confidence(a))
+        })
+    }
+}
+
+/// Keeps extractions that appear in at least `min_votes` of `samples`, taking
+/// the first occurrence as representative and setting its confidence to the
+/// fraction of samples that contain it.
+pub fn vote(samples: &[Vec<Extraction>], min_votes: usize) -> Vec<Extraction> {
+    let mut votes: HashMap<SpanKey, (Extraction, usize)> = HashMap::new();
+    let mut order = Vec::new();
+    for sample in samples {
+        let mut counted = HashSet::new();
+        for extraction in sample {
+            let key = SpanKey::of(extraction);
+            if !counted.insert(key.clone()) {
+                continue;
+            }
+            match votes.get_mut(&key) {
+                Some((_, count)) => *count += 1,
+                None => {
+                    order.push(key.clone());
+                    votes.insert(key, (extraction.clone(), 1));
+                }
+            }
+        }
+    }
+
+    let total = samples.len().max(1) as f64;
+    let kept = order
+        .into_iter()
+        .filter_map(|key| votes.remove(&key))
+        .filter(|(_, count)| *count >= min_votes)
+        .map(|(mut extraction, count)| {
+            extraction.confidence = Some(count as f64 / total);
+            extraction
+        })
+        .collect();
+    sorted_by_position(kept)
+}
+
+/// Like [`vote`], but two aligned extractions agree when they have the same
+/// class and overlapping spans rather than identical ones, so samples that cut
+/// an entity slightly differently still vote for it. Unaligned extractions
+/// agree on class and text. The `model_ids` of agreeing extractions are
+/// combined on the kept one.
+pub fn vote_overlapping(samples: &[Vec<Extraction>], min_votes: usize) -> Vec<Extraction> {
+    // Each candidate holds its representative, its vote count and the last
+    // sample that voted for it, so a sample votes at most once per candidate.
+    let mut candidates: Vec<(Extraction, usize, usize)> = Vec::new();
+    for (sample_index, sample) in samples.iter().enumerate() {
+        for extraction in sample {
+            let agrees = |representative: &Extraction| {
+                representative.extraction_class == extraction.extraction_class
+                    && match (position(representative), position(extraction)) {
+                        (Some(_), Some(_)) => extractions_overlap(representative, extraction),
+                        (None, None) => representative.extraction_text == extraction.extraction_text,
+                        _ => false,
+                    }
+            };
+            match candidates
+                .iter()
+                .position(|(representative, _, last_sample)| *last_sample != sample_index && agrees(representative))
+            {
+                Some(index) => {
+                    let (representative, count, last_sample) = &mut candidates[index];
+                    *count += 1;
+                    *last_sample = sample_index;
+                    for model_id in &extraction.model_ids {
+                        if !representative.model_ids.contains(model_id) {
+                            representative.model_ids.push(model_id.clone());
+                        }
+                    }
+                }
+                // A second mention of something this sample already voted for.
+                None if candidates
+                    .iter()
+                    .any(|(representative, _, last_sample)| *last_sample == sample_index && agrees(representative)) => {
+                }
+                None => candidates.push((extraction.clone(), 1, sample_index)),
+            }
+        }
+    }
+
+    let total = samples.len().max(1) as f64;
+    let kept = candidates
+        .into_iter()
+        .filter(|(_, count, _)| *count >= min_votes)
+        .map(|(mut extraction, count, _)| {
+            extraction.confidence = Some(count as f64 / total);
+            extraction
+        })
+        .collect();
+    sorted_by_position(kept)
+}
+
+/// Identity of an extraction for deduplication and voting.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SpanKey {
+    Aligned(String, usize, usize),
+    Unaligned(String, String),
+}
+
+impl SpanKey {
+    fn of(extraction: &Extraction) -> Self {
+        match position(extraction) {
+            Some((start, end)) => SpanKey::Aligned(extraction.extraction_class.clone(), start, end),
+            None => SpanKey::Unaligned(extraction.extraction_class.clone(), extraction.extraction_text.clone()),
+        }
+    }
+}
+
+fn position(extraction: &Extraction) -> Option<(usize, usize)> {
+    let interval = extraction.char_interval.as_ref()?;
+    Some((interval.start_pos?, interval.end_pos?))
+}
+
+fn span_len(extraction: &Extraction) -> usize {
+    position(extraction).map_or(0, |(start, end)| end.saturating_sub(start))
+}
+
+/// Greedily keeps the best-ranked extractions that do not overlap anything
+/// already kept. Candidates are ranked by `rank`, then by pass order.
+fn select_non_overlapping(
+    pass_extractions: &[Vec<Extraction>],
+    rank: impl Fn(&Extraction, &Extraction) -> Ordering,
+) -> Vec<Extraction> {
+    let mut candidates: Vec<&Extraction> = pass_extractions.iter().flatten().collect();
+    candidates.sort_by(|a, b| rank(a, b));
+
+    let mut kept: Vec<Extraction> = Vec::new();
+    let mut unaligned = HashSet::new();
+    for candidate in candidates {
+        if position(candidate).is_none() {
+            if unaligned.insert(SpanKey::of(candidate)) {
+                kept.push(candidate.clone());
+            }
+        } else if !kept.iter().any(|existing| extractions_overlap(candidate, existing)) {
+            kept.push(candidate.clone());
+        }
+    }
+    sorted_by_position(kept)
+}
+
+/// Orders extractions by start and end position, unaligned ones last, keeping
+/// the existing order among equal positions.
+pub fn sorted_by_position(mut extractions: Vec<Extraction>) -> Vec<Extraction> {
+    extractions.sort_by_key(|e| position(e).unwrap_or((usize::MAX, usize::MAX)));
+    extractions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharInterval;
+
+    fn extraction(class: &str, text: &str, start: usize, end: usize) -> Extraction {
+        Extraction::new(
+            class.to_string(),
+            text.to_string(),
+            None,
+            Some(CharInterval::new(Some(start), Some(end))),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn texts(extractions: &[Extraction]) -> Vec<&str> {
+        extractions.iter().map(|e| e.extraction_text.as_str()).collect()
+    }
+
+    fn passes() -> Vec<Vec<Extraction>> {
+        vec![
+            vec![extraction("person", "Bob", 20, 23), extraction("person", "Alice", 0, 5)],
+            vec![
+                extraction("person", "Alice Smith", 0, 11),
+                extraction("person", "Bob", 20, 23),
+                extraction("place", "Paris", 30, 35),
+            ],
+            vec![
+                extraction("person", "Alice", 0, 5),
+                extraction("person", "Carol", 40, 45),
+            ],
+        ]
+    }
+
+    #[test]
+    fn test_first_wins_orders_by_position() {
+        let merged = FirstWins.merge(&passes());
+        assert_eq!(texts(&merged), vec!["Alice", "Bob", "Paris", "Carol"]);
+    }
+
+    #[test]
+    fn test_union_drops_exact_duplicates() {
+        let merged = Union.merge(&passes());
+        assert_eq!(texts(&merged), vec!["Alice", "Alice Smith", "Bob", "Paris", "Carol"]);
+    }
+
+    #[test]
+    fn test_majority_vote_sets_agreement_confidence() {
+        let merged = MajorityVote::default().merge(&passes());
+        assert_eq!(texts(&merged), vec!["Alice", "Bob"]);
+        assert!(merged.iter().all(|e| (e.confidence.unwrap() - 2.0 / 3.0).abs() < 1e-9));
+
+        let merged = MajorityVote::with_min_votes(1).merge(&passes());
+        assert_eq!(merged.len(), 5);
+    }
+
+    #[test]
+    fn test_vote_overlapping_counts_overlapping_spans() {
+        let samples = vec![
+            vec![extraction("person", "Alice", 0, 5), extraction("person", "Bob", 20, 23)],
+            vec![
+                extraction("person", "Alice Smith", 0, 11),
+                extraction("place", "Bob", 20, 23),
+            ],
+            vec![
+                extraction("person", "Alice", 0, 5),
+                extraction("person", "Smith", 6, 11),
+            ],
+        ];
+        let voted = vote_overlapping(&samples, 2);
+        assert_eq!(texts(&voted), vec!["Alice"]);
+        assert_eq!(voted[0].confidence, Some(1.0));
+
+        let voted = vote_overlapping(&samples, 1);
+        assert_eq!(texts(&voted), vec!["Alice", "Smith", "Bob", "Bob"]);
+    }
+
+    #[test]
+    fn test_longest_span_prefers_longer_overlap() {
+        let merged = LongestSpan.merge(&passes());
+        assert_eq!(texts(&merged), vec!["Alice Smith", "Bob", "Paris", "Carol"]);
+    }
+
+    #[test]
+    fn test_highest_confidence_prefers_confident_extraction() {
+        let mut passes = passes();
+        passes[2][0].confidence = Some(0.9);
+        passes[1][0].confidence = Some(0.4);
+        let merged = HighestConfidence.merge(&passes);
+        assert_eq!(texts(&merged), vec!["Alice", "Bob", "Paris", "Carol"]);
+        assert_eq!(merged[0].confidence, Some(0.9));
+    }
+}
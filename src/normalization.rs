@@ -0,0 +1,468 @@
+/*!
+Token normalization for alignment.
+
+Alignment compares extraction and source tokens after normalizing them, so
+that "Running" and "runs", "ＡＢＣ" and "ABC", or "國" and "国" count as the
+same token. What should be folded depends on the language and the documents,
+so the normalization is a `TokenNormalizer` chosen per run with
+`Resolver::with_token_normalizer`, which applies it in every match tier.
+
+The built-in normalizers each do one thing and compose with
+`NormalizerChain`:
+
+- `Nfkc`: Unicode NFKC compatibility normalization.
+- `CaseFold`: Unicode case folding.
+- `StripDiacritics`: removes accents and other combining marks.
+- `FullWidthToHalfWidth`: maps full-width ASCII forms to ASCII.
+- `PorterStemmer`: reduces English words to their Porter stem.
+- `ChineseVariantFolding`: maps Traditional Chinese characters to Simplified.
+
+Without one, tokens are only lowercased, and fuzzy alignment also case folds
+and stems them with `NormalizerChain::standard()`.
+
+Usage example:
+    let normalizer = NormalizerChain::new()
+        .with(Nfkc)
+        .with(CaseFold)
+        .with(StripDiacritics)
+        .with(ChineseVariantFolding);
+    let resolver = Resolver::default().with_token_normalizer(normalizer);
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use lazy_static::lazy_static;
+
+/// Maps a token to the form compared during alignment.
+///
+/// Two tokens are considered equal when their normalized forms are equal.
+pub trait TokenNormalizer: fmt::Debug + Send + Sync {
+    fn normalize(&self, token: &str) -> String;
+}
+
+impl<T: TokenNormalizer + ?Sized> TokenNormalizer for Arc<T> {
+    fn normalize(&self, token: &str) -> String {
+        (**self).normalize(token)
+    }
+}
+
+impl<T: TokenNormalizer + ?Sized> TokenNormalizer for Box<T> {
+    fn normalize(&self, token: &str) -> String {
+        (**self).normalize(token)
+    }
+}
+
+/// Applies several normalizers in order.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizerChain {
+    normalizers: Vec<Arc<dyn TokenNormalizer>>,
+}
+
+impl NormalizerChain {
+    /// An empty chain, which leaves tokens unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Case folding followed by Porter stemming, the alignment default.
+    pub fn standard() -> Self {
+        Self::new().with(CaseFold).with(PorterStemmer)
+    }
+
+    /// Appends a normalizer to the chain.
+    pub fn with(mut self, normalizer: impl TokenNormalizer + 'static) -> Self {
+        self.normalizers.push(Arc::new(normalizer));
+        self
+    }
+}
+
+impl TokenNormalizer for NormalizerChain {
+    fn normalize(&self, token: &str) -> String {
+        self.normalizers
+            .iter()
+            .fold(token.to_string(), |token, normalizer| normalizer.normalize(&token))
+    }
+}
+
+/// Unicode NFKC normalization: compatibility characters such as ligatures,
+/// full-width forms and circled digits become their plain equivalents.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nfkc;
+
+impl TokenNormalizer for Nfkc {
+    fn normalize(&self, token: &str) -> String {
+        ComposingNormalizerBorrowed::new_nfkc().normalize(token).into_owned()
+    }
+}
+
+/// Unicode case folding, for caseless comparison.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseFold;
+
+impl TokenNormalizer for CaseFold {
+    fn normalize(&self, token: &str) -> String {
+        let mut folded = String::with_capacity(token.len());
+        for c in token.chars() {
+            match c {
+                // Full folding differs from lowercasing for these.
+                'ß' | 'ẞ' => folded.push_str("ss"),
+                'ς' => folded.push('σ'),
+                _ => folded.extend(c.to_lowercase()),
+            }
+        }
+        folded
+    }
+}
+
+/// Removes diacritics by decomposing the token and dropping combining marks,
+/// so "café" and "cafe" compare equal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripDiacritics;
+
+impl TokenNormalizer for StripDiacritics {
+    fn normalize(&self, token: &str) -> String {
+        DecomposingNormalizerBorrowed::new_nfd()
+            .normalize(token)
+            .chars()
+            .filter(|&c| !is_combining_mark(c))
+            .collect()
+    }
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Maps full-width ASCII forms (U+FF01 to U+FF5E) and the ideographic space
+/// to their ASCII equivalents, leaving other characters alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FullWidthToHalfWidth;
+
+impl TokenNormalizer for FullWidthToHalfWidth {
+    fn normalize(&self, token: &str) -> String {
+        token
+            .chars()
+            .map(|c| match c {
+                '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+                '\u{3000}' => ' ',
+                _ => c,
+            })
+            .collect()
+    }
+}
+
+/// The Porter (1980) stemmer for English.
+///
+/// Tokens that are not entirely ASCII letters are returned unchanged; others
+/// are lowercased and stemmed, so "connection", "connected" and "connecting"
+/// all become "connect".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PorterStemmer;
+
+impl TokenNormalizer for PorterStemmer {
+    fn normalize(&self, token: &str) -> String {
+        if token.is_empty() || !token.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return token.to_string();
+        }
+        porter_stem(&token.to_ascii_lowercase())
+    }
+}
+
+/// Maps Traditional Chinese characters to their Simplified forms, so text
+/// written in either script aligns with the other. Folding is character by
+/// character: a character whose Simplified form depends on the word, such as
+/// 乾 (干 or 乾) or 後 (后), always folds to its most common Simplified form.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChineseVariantFolding;
+
+impl TokenNormalizer for ChineseVariantFolding {
+    fn normalize(&self, token: &str) -> String {
+        token
+            .chars()
+            .map(|c| TRADITIONAL_TO_SIMPLIFIED.get(&c).copied().unwrap_or(c))
+            .collect()
+    }
+}
+
+/// Traditional to Simplified character pairs covering all CJK Unified Ideographs, generated from
+/// the ICU Traditional-Simplified transform; the file header describes how.
+const TRADITIONAL_SIMPLIFIED_PAIRS: &str = include_str!("normalization/ts_characters.txt");
+
+lazy_static! {
+    static ref TRADITIONAL_TO_SIMPLIFIED: HashMap<char, char> = TRADITIONAL_SIMPLIFIED_PAIRS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (traditional, simplified) = line.split_once('\t')?;
+            Some((traditional.chars().next()?, simplified.chars().next()?))
+        })
+        .collect();
+}
+
+/// ----------------------------
+/// Porter stemmer
+/// ----------------------------
+fn porter_stem(word: &str) -> String {
+    if word.len() <= 2 {
+        return word.to_string();
+    }
+    let mut w = word.as_bytes().to_vec();
+    step_1a(&mut w);
+    step_1b(&mut w);
+    step_1c(&mut w);
+    step_2(&mut w);
+    step_3(&mut w);
+    step_4(&mut w);
+    step_5(&mut w);
+    String::from_utf8(w).unwrap_or_else(|_| word.to_string())
+}
+
+/// Whether `w[i]` is a consonant; "y" is one unless it follows a consonant.
+fn is_consonant(w: &[u8], i: usize) -> bool {
+    match w[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(w, i - 1),
+        _ => true,
+    }
+}
+
+/// The measure m of `stem`: the number of vowel-consonant sequences.
+fn measure(stem: &[u8]) -> usize {
+    let mut m = 0;
+    let mut prev_vowel = false;
+    for i in 0..stem.len() {
+        let consonant = is_consonant(stem, i);
+        if consonant && prev_vowel {
+            m += 1;
+        }
+        prev_vowel = !consonant;
+    }
+    m
+}
+
+fn contains_vowel(stem: &[u8]) -> bool {
+    (0..stem.len()).any(|i| !is_consonant(stem, i))
+}
+
+fn ends_double_consonant(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 2 && w[n - 1] == w[n - 2] && is_consonant(w, n - 1)
+}
+
+/// Whether `w` ends consonant-vowel-consonant, the last not w, x or y.
+fn ends_cvc(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 3
+        && is_consonant(w, n - 3)
+        && !is_consonant(w, n - 2)
+        && is_consonant(w, n - 1)
+        && !matches!(w[n - 1], b'w' | b'x' | b'y')
+}
+
+/// Replaces `suffix` with `replacement` when `w` ends with it and the
+/// remaining stem passes `condition`. Returns whether `w` ended with it.
+fn replace_suffix(w: &mut Vec<u8>, suffix: &str, replacement: &str, condition: impl Fn(&[u8]) -> bool) -> bool {
+    if !w.ends_with(suffix.as_bytes()) {
+        return false;
+    }
+    let stem_len = w.len() - suffix.len();
+    if condition(&w[..stem_len]) {
+        w.truncate(stem_len);
+        w.extend_from_slice(replacement.as_bytes());
+    }
+    true
+}
+
+/// Applies the first rule of `rules` whose suffix `w` ends with, if its stem
+/// has a measure above `min_measure`.
+fn apply_rules(w: &mut Vec<u8>, rules: &[(&str, &str)], min_measure: usize) {
+    for (suffix, replacement) in rules {
+        if replace_suffix(w, suffix, replacement, |stem| measure(stem) > min_measure) {
+            return;
+        }
+    }
+}
+
+fn step_1a(w: &mut Vec<u8>) {
+    for (suffix, replacement) in [("sses", "ss"), ("ies", "i"), ("ss", "ss"), ("s", "")] {
+        if replace_suffix(w, suffix, replacement, |_| true) {
+            return;
+        }
+    }
+}
+
+fn step_1b(w: &mut Vec<u8>) {
+    if w.ends_with(b"eed") {
+        replace_suffix(w, "eed", "ee", |stem| measure(stem) > 0);
+        return;
+    }
+    let removed = ["ed", "ing"].into_iter().any(|suffix| {
+        w.ends_with(suffix.as_bytes()) && contains_vowel(&w[..w.len() - suffix.len()]) && {
+            w.truncate(w.len() - suffix.len());
+            true
+        }
+    });
+    if !removed {
+        return;
+    }
+    if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+        w.push(b'e');
+    } else if ends_double_consonant(w) && !matches!(w[w.len() - 1], b'l' | b's' | b'z') {
+        w.pop();
+    } else if measure(w) == 1 && ends_cvc(w) {
+        w.push(b'e');
+    }
+}
+
+fn step_1c(w: &mut [u8]) {
+    let n = w.len();
+    if n >= 2 && w[n - 1] == b'y' && contains_vowel(&w[..n - 1]) {
+        w[n - 1] = b'i';
+    }
+}
+
+fn step_2(w: &mut Vec<u8>) {
+    const RULES: &[(&str, &str)] = &[
+        ("ational", "ate"),
+        ("tional", "tion"),
+        ("enci", "ence"),
+        ("anci", "ance"),
+        ("izer", "ize"),
+        ("bli", "ble"),
+        ("alli", "al"),
+        ("entli", "ent"),
+        ("eli", "e"),
+        ("ousli", "ous"),
+        ("ization", "ize"),
+        ("ation", "ate"),
+        ("ator", "ate"),
+        ("alism", "al"),
+        ("iveness", "ive"),
+        ("fulness", "ful"),
+        ("ousness", "ous"),
+        ("aliti", "al"),
+        ("iviti", "ive"),
+        ("biliti", "ble"),
+        ("logi", "log"),
+    ];
+    apply_rules(w, RULES, 0);
+}
+
+fn step_3(w: &mut Vec<u8>) {
+    const RULES: &[(&str, &str)] = &[
+        ("icate", "ic"),
+        ("ative", ""),
+        ("alize", "al"),
+        ("iciti", "ic"),
+        ("ical", "ic"),
+        ("ful", ""),
+        ("ness", ""),
+    ];
+    apply_rules(w, RULES, 0);
+}
+
+fn step_4(w: &mut Vec<u8>) {
+    const SUFFIXES: &[&str] = &[
+        "ement", "ance", "ence", "able", "ible", "ment", "ant", "ent", "ism", "ate", "iti", "ous", "ive", "ize", "ion",
+        "al", "er", "ic", "ou",
+    ];
+    for suffix in SUFFIXES {
+        let matched = if *suffix == "ion" {
+            replace_suffix(w, suffix, "", |stem| {
+                measure(stem) > 1 && matches!(stem.last(), Some(b's') | Some(b't'))
+            })
+        } else {
+            replace_suffix(w, suffix, "", |stem| measure(stem) > 1)
+        };
+        if matched {
+            return;
+        }
+    }
+}
+
+fn step_5(w: &mut Vec<u8>) {
+    if w.ends_with(b"e") {
+        let stem = &w[..w.len() - 1];
+        let m = measure(stem);
+        if m > 1 || (m == 1 && !ends_cvc(stem)) {
+            w.pop();
+        }
+    }
+    if w.ends_with(b"ll") && measure(w) > 1 {
+        w.pop();
+    }
+}
+
+/// ----------------------------
+/// Tests
+/// ----------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_porter_stemmer() {
+        let cases = [
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("cats", "cat"),
+            ("feed", "feed"),
+            ("agreed", "agre"),
+            ("plastered", "plaster"),
+            ("motoring", "motor"),
+            ("sing", "sing"),
+            ("conflated", "conflat"),
+            ("troubled", "troubl"),
+            ("sized", "size"),
+            ("hopping", "hop"),
+            ("falling", "fall"),
+            ("filing", "file"),
+            ("happy", "happi"),
+            ("relational", "relat"),
+            ("generalization", "gener"),
+            ("connection", "connect"),
+            ("Connecting", "connect"),
+            ("running", "run"),
+            ("glass", "glass"),
+            ("news", "new"),
+            ("controll", "control"),
+            ("adoption", "adopt"),
+            ("x-ray", "x-ray"),
+        ];
+        for (word, stem) in cases {
+            assert_eq!(PorterStemmer.normalize(word), stem, "stem of {word}");
+        }
+    }
+
+    #[test]
+    fn test_unicode_normalizers() {
+        assert_eq!(Nfkc.normalize("ﬁle"), "file");
+        assert_eq!(Nfkc.normalize("ＡＢＣ１"), "ABC1");
+        assert_eq!(CaseFold.normalize("Straße"), "strasse");
+        assert_eq!(CaseFold.normalize("ΣΟΦΟΣ"), "σοφοσ");
+        assert_eq!(StripDiacritics.normalize("Crème Brûlée"), "Creme Brulee");
+        assert_eq!(FullWidthToHalfWidth.normalize("ＡＢＣ！　x"), "ABC! x");
+        assert_eq!(FullWidthToHalfWidth.normalize("袭人"), "袭人");
+        assert_eq!(ChineseVariantFolding.normalize("襲人在怡紅院"), "袭人在怡红院");
+        assert_eq!(ChineseVariantFolding.normalize("廣熱報準憶觸"), "广热报准忆触");
+        assert_eq!(ChineseVariantFolding.normalize("abc"), "abc");
+    }
+
+    #[test]
+    fn test_normalizer_chain() {
+        assert_eq!(NormalizerChain::new().normalize("Runs"), "Runs");
+        assert_eq!(NormalizerChain::standard().normalize("Runners"), "runner");
+        let chain = NormalizerChain::new().with(Nfkc).with(CaseFold).with(StripDiacritics);
+        assert_eq!(chain.normalize("ＣＡＦÉ"), "cafe");
+    }
+}
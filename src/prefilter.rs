@@ -0,0 +1,172 @@
+/*!
+Relevance pre-filtering of chunks before extraction.
+
+Most chunks of a long document often contain none of the target classes, yet
+each one pays for a full few-shot prompt. A `RelevanceFilter` decides up front
+whether a chunk is worth extracting from; chunks that fail any registered
+filter are skipped and listed in `AnnotatedDocument::skipped_chunks`.
+
+Usage example:
+    let annotator = Annotator::new(language_model, prompt_template, format_type, None, true)
+        .with_relevance_filter(KeywordFilter::new(["aspirin", "ibuprofen"]))
+        .with_relevance_filter(ModelFilter::new(cheap_model, "mentions of medication"));
+*/
+
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+
+use crate::inference::{BaseLanguageModel, InferenceOutputError, split_reasoning};
+
+/// Decides whether a chunk may contain anything to extract.
+#[async_trait]
+pub trait RelevanceFilter: Send + Sync {
+    async fn is_relevant(&self, chunk_text: &str) -> Result<bool, InferenceOutputError>;
+}
+
+/// Passes chunks that match at least one keyword or regular expression.
+#[derive(Debug, Clone)]
+pub struct KeywordFilter {
+    patterns: Vec<Regex>,
+}
+
+impl KeywordFilter {
+    /// Matches any of `keywords` literally, ignoring case.
+    pub fn new<I, S>(keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = keywords
+            .into_iter()
+            .map(|keyword| {
+                RegexBuilder::new(&regex::escape(keyword.as_ref()))
+                    .case_insensitive(true)
+                    .build()
+                    .expect("escaped keyword is a valid regex")
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Matches any of the regular expressions in `patterns`.
+    pub fn from_regex<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| Regex::new(pattern.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+}
+
+#[async_trait]
+impl RelevanceFilter for KeywordFilter {
+    async fn is_relevant(&self, chunk_text: &str) -> Result<bool, InferenceOutputError> {
+        Ok(self.patterns.iter().any(|pattern| pattern.is_match(chunk_text)))
+    }
+}
+
+/// Asks a (typically small and cheap) language model a yes/no question about
+/// each chunk.
+pub struct ModelFilter<L: BaseLanguageModel> {
+    language_model: L,
+    target: String,
+}
+
+impl<L: BaseLanguageModel> ModelFilter<L> {
+    /// `target` describes what is being extracted, e.g. "people and their
+    /// roles".
+    pub fn new(language_model: L, target: impl Into<String>) -> Self {
+        Self {
+            language_model,
+            target: target.into(),
+        }
+    }
+
+    fn prompt(&self, chunk_text: &str) -> String {
+        format!(
+            "Does the following text contain any {}? Answer with yes or no.\n\nText:\n{}\n\nAnswer:",
+            self.target, chunk_text
+        )
+    }
+}
+
+#[async_trait]
+impl<L: BaseLanguageModel> RelevanceFilter for ModelFilter<L> {
+    async fn is_relevant(&self, chunk_text: &str) -> Result<bool, InferenceOutputError> {
+        let outputs = self.language_model.infer(&[self.prompt(chunk_text)], None).await?;
+        let answer = outputs
+            .first()
+            .and_then(|scored| scored.first())
+            .and_then(|scored| scored.output.as_deref())
+            .unwrap_or_default();
+        let (_, answer) = split_reasoning(answer);
+        let first_word = answer.split(|c: char| !c.is_alphabetic()).find(|word| !word.is_empty());
+        Ok(!first_word.is_some_and(|word| word.eq_ignore_ascii_case("no")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::inference::ScoredOutput;
+
+    struct FixedModel(&'static str);
+
+    #[async_trait]
+    impl BaseLanguageModel for FixedModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: Option<HashMap<String, serde_json::Value>>,
+        ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+            Ok(batch_prompts
+                .iter()
+                .map(|_| vec![ScoredOutput::new(Some(1.0), Some(self.0.to_string()))])
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keyword_filter_ignores_case() {
+        let filter = KeywordFilter::new(["Aspirin", "c++"]);
+        assert!(filter.is_relevant("Took ASPIRIN daily.").await.unwrap());
+        assert!(filter.is_relevant("Written in C++.").await.unwrap());
+        assert!(!filter.is_relevant("Nothing here.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_regex_filter() {
+        let filter = KeywordFilter::from_regex([r"\b\d+\s?mg\b"]).unwrap();
+        assert!(filter.is_relevant("Give 200 mg twice.").await.unwrap());
+        assert!(!filter.is_relevant("Give it twice.").await.unwrap());
+        assert!(KeywordFilter::from_regex(["("]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_model_filter_reads_yes_and_no() {
+        let relevant = ModelFilter::new(FixedModel("Yes."), "medication");
+        assert!(relevant.is_relevant("Took aspirin.").await.unwrap());
+        let irrelevant = ModelFilter::new(FixedModel(" No, it does not."), "medication");
+        assert!(!irrelevant.is_relevant("Went for a walk.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_model_filter_ignores_reasoning() {
+        let irrelevant = ModelFilter::new(
+            FixedModel("<think>Nothing here names a drug.</think>\nNo"),
+            "medication",
+        );
+        assert!(!irrelevant.is_relevant("Went for a walk.").await.unwrap());
+        let relevant = ModelFilter::new(
+            FixedModel("<think>No dosage, but aspirin is named.</think> yes"),
+            "medication",
+        );
+        assert!(relevant.is_relevant("Took aspirin.").await.unwrap());
+    }
+}
@@ -1,8 +1,13 @@
 use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
 use std::time::Duration;
 use indicatif::{ProgressBar, ProgressStyle, ProgressIterator, ProgressDrawTarget};
 use url::Url;
 
+use crate::data::{AnnotatedDocument, CharInterval};
+use crate::inference::InferenceOutputError;
+
 // ANSI color codes
 const BLUE: &str = "\x1b[94m";
 const GREEN: &str = "\x1b[92m";
@@ -216,6 +221,237 @@ pub fn format_extraction_progress(
     desc
 }
 
+/// Receives events while an [`Annotator`](crate::annotation::Annotator) runs.
+///
+/// Every callback has a no-op default, so implementations only override the
+/// events they care about. Observers are shared by the annotator across
+/// awaits and must therefore be `Send + Sync`.
+pub trait AnnotationObserver: Send + Sync {
+    /// Called once before any inference, with the number of characters that
+    /// will be sent to the model over all passes, when it is known up front.
+    fn on_run_start(&self, _total_chars: Option<usize>) {}
+
+    /// Called before a batch of chunks is sent to the language model.
+    fn on_batch_start(&self, _batch_index: usize, _batch_size: usize, _batch_chars: usize) {}
+
+    /// Called after the output for a chunk has been resolved and aligned.
+    fn on_chunk_done(&self, _document_id: &str, _char_interval: &CharInterval, _num_extractions: usize) {}
+
+    /// Called with the source characters a finished or skipped chunk advances
+    /// the run by. Text repeated from the previous chunk is not counted again,
+    /// so over a run these add up to the total given to `on_run_start`.
+    fn on_chars_processed(&self, _chars: usize) {}
+
+    /// Called when a relevance filter rejects a chunk, which is then not sent
+    /// to the language model.
+    fn on_chunk_skipped(&self, _document_id: &str, _char_interval: &CharInterval) {}
+
+    /// Called with each finished document, after all of its passes are merged.
+    fn on_document_done(&self, _document: &AnnotatedDocument) {}
+
+    /// Called when inference or resolution fails.
+    fn on_error(&self, _error: &InferenceOutputError) {}
+
+    /// Called after each extraction pass over a document.
+    fn on_pass_done(&self, _document_id: &str, _pass_index: usize, _total_passes: usize) {}
+
+    /// Called once after the last document has been produced.
+    fn on_run_done(&self) {}
+}
+
+/// Renders annotation progress as an indicatif bar with throughput and ETA.
+///
+/// Progress is measured in characters of source text sent to the model. When
+/// the total is not known up front the bar falls back to a spinner.
+pub struct IndicatifObserver {
+    progress_bar: ProgressBar,
+    model_info: Option<String>,
+    processed_chars: AtomicUsize,
+}
+
+impl IndicatifObserver {
+    /// * `model_info` - Model information shown in the bar (optional)
+    /// * `disable` - Whether to disable progress bar
+    pub fn new(model_info: Option<&str>, disable: bool) -> Self {
+        let progress_bar = ProgressBar::new_spinner();
+        if disable {
+            progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        Self {
+            progress_bar,
+            model_info: model_info.map(|s| s.to_string()),
+            processed_chars: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the underlying progress bar.
+    pub fn progress_bar(&self) -> &ProgressBar {
+        &self.progress_bar
+    }
+}
+
+impl AnnotationObserver for IndicatifObserver {
+    fn on_run_start(&self, total_chars: Option<usize>) {
+        self.processed_chars.store(0, Ordering::Relaxed);
+        self.progress_bar.reset();
+        match total_chars {
+            Some(total) => {
+                self.progress_bar.set_length(total as u64);
+                self.progress_bar.set_style(
+                    ProgressStyle::with_template(
+                        "{msg} [{bar:40}] {pos}/{len} chars [{elapsed}<{eta}, {per_sec}]",
+                    )
+                    .unwrap()
+                    .progress_chars("=>-"),
+                );
+            }
+            None => {
+                self.progress_bar.set_style(
+                    ProgressStyle::with_template("{spinner} {msg} [{elapsed}, {per_sec}]")
+                        .unwrap()
+                        .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", "✓"]),
+                );
+                self.progress_bar.enable_steady_tick(Duration::from_millis(100));
+            }
+        }
+        self.progress_bar
+            .set_message(format_extraction_progress(self.model_info.as_deref(), None, None));
+    }
+
+    fn on_batch_start(&self, _batch_index: usize, _batch_size: usize, batch_chars: usize) {
+        self.progress_bar.set_message(format_extraction_progress(
+            self.model_info.as_deref(),
+            Some(batch_chars),
+            Some(self.processed_chars.load(Ordering::Relaxed)),
+        ));
+    }
+
+    fn on_chars_processed(&self, chars: usize) {
+        self.processed_chars.fetch_add(chars, Ordering::Relaxed);
+        self.progress_bar.inc(chars as u64);
+    }
+
+    fn on_error(&self, error: &InferenceOutputError) {
+        self.progress_bar.println(format!("{}✗{} {}", BOLD, RESET, error));
+    }
+
+    fn on_run_done(&self) {
+        self.progress_bar.finish();
+    }
+}
+
+/// An annotation event, as forwarded by [`ChannelObserver`].
+#[derive(Debug, Clone)]
+pub enum AnnotationEvent {
+    RunStart {
+        total_chars: Option<usize>,
+    },
+    BatchStart {
+        batch_index: usize,
+        batch_size: usize,
+        batch_chars: usize,
+    },
+    ChunkDone {
+        document_id: String,
+        char_interval: CharInterval,
+        num_extractions: usize,
+    },
+    ChunkSkipped {
+        document_id: String,
+        char_interval: CharInterval,
+    },
+    CharsProcessed {
+        chars: usize,
+    },
+    DocumentDone {
+        document: AnnotatedDocument,
+    },
+    Error {
+        message: String,
+    },
+    PassDone {
+        document_id: String,
+        pass_index: usize,
+        total_passes: usize,
+    },
+    RunDone,
+}
+
+/// Forwards every annotation event over a channel, so services can push
+/// progress to their own consumers (websockets, job tables, logs).
+///
+/// Events are dropped silently once the receiving side hangs up.
+pub struct ChannelObserver {
+    sender: Sender<AnnotationEvent>,
+}
+
+impl ChannelObserver {
+    pub fn new(sender: Sender<AnnotationEvent>) -> Self {
+        Self { sender }
+    }
+
+    fn send(&self, event: AnnotationEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+impl AnnotationObserver for ChannelObserver {
+    fn on_run_start(&self, total_chars: Option<usize>) {
+        self.send(AnnotationEvent::RunStart { total_chars });
+    }
+
+    fn on_batch_start(&self, batch_index: usize, batch_size: usize, batch_chars: usize) {
+        self.send(AnnotationEvent::BatchStart {
+            batch_index,
+            batch_size,
+            batch_chars,
+        });
+    }
+
+    fn on_chunk_done(&self, document_id: &str, char_interval: &CharInterval, num_extractions: usize) {
+        self.send(AnnotationEvent::ChunkDone {
+            document_id: document_id.to_string(),
+            char_interval: char_interval.clone(),
+            num_extractions,
+        });
+    }
+
+    fn on_chunk_skipped(&self, document_id: &str, char_interval: &CharInterval) {
+        self.send(AnnotationEvent::ChunkSkipped {
+            document_id: document_id.to_string(),
+            char_interval: char_interval.clone(),
+        });
+    }
+
+    fn on_chars_processed(&self, chars: usize) {
+        self.send(AnnotationEvent::CharsProcessed { chars });
+    }
+
+    fn on_document_done(&self, document: &AnnotatedDocument) {
+        self.send(AnnotationEvent::DocumentDone {
+            document: document.clone(),
+        });
+    }
+
+    fn on_error(&self, error: &InferenceOutputError) {
+        self.send(AnnotationEvent::Error {
+            message: error.to_string(),
+        });
+    }
+
+    fn on_pass_done(&self, document_id: &str, pass_index: usize, total_passes: usize) {
+        self.send(AnnotationEvent::PassDone {
+            document_id: document_id.to_string(),
+            pass_index,
+            total_passes,
+        });
+    }
+
+    fn on_run_done(&self) {
+        self.send(AnnotationEvent::RunDone);
+    }
+}
+
 // --- Helper functions ---
 
 /// Truncates URL for display purposes
@@ -282,6 +518,44 @@ mod tests {
         assert_eq!(wrapped.collect::<Vec<_>>(), vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_indicatif_observer_tracks_processed_chars() {
+        let observer = IndicatifObserver::new(Some("model"), true);
+        observer.on_run_start(Some(100));
+        observer.on_batch_start(0, 2, 40);
+        observer.on_chunk_done("doc", &CharInterval::new(Some(0), Some(25)), 3);
+        observer.on_chars_processed(25);
+        observer.on_chunk_done("doc", &CharInterval::new(Some(20), Some(45)), 0);
+        observer.on_chars_processed(20);
+        assert_eq!(observer.progress_bar().position(), 45);
+        assert_eq!(observer.progress_bar().length(), Some(100));
+        observer.on_run_done();
+        assert!(observer.progress_bar().is_finished());
+    }
+
+    #[test]
+    fn test_channel_observer_forwards_events() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let observer = ChannelObserver::new(sender);
+        observer.on_run_start(None);
+        observer.on_pass_done("doc", 0, 2);
+        observer.on_error(&InferenceOutputError::new("boom"));
+        drop(observer);
+
+        let events: Vec<_> = receiver.iter().collect();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], AnnotationEvent::RunStart { total_chars: None }));
+        assert!(matches!(
+            events[1],
+            AnnotationEvent::PassDone {
+                pass_index: 0,
+                total_passes: 2,
+                ..
+            }
+        ));
+        assert!(matches!(&events[2], AnnotationEvent::Error { message } if message.contains("boom")));
+    }
+
     #[test]
     fn test_extraction_summary() {
         // Test without performance metrics
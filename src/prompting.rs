@@ -98,6 +98,8 @@ pub struct QAPromptGenerator {
     pub question_prefix: String,
     pub answer_prefix: String,
     pub fence_output: bool,
+    /// Introduces the extractions earlier passes found in the question text.
+    pub prior_extractions_heading: String,
 }
 
 impl Default for QAPromptGenerator {
@@ -113,6 +115,9 @@ impl Default for QAPromptGenerator {
             question_prefix: "Q: ".to_string(),
             answer_prefix: "A: ".to_string(),
             fence_output: true,
+            prior_extractions_heading:
+                "Already extracted from the text below. Return only additional extractions that are not listed:"
+                    .to_string(),
         }
     }
 }
@@ -201,6 +206,17 @@ impl QAPromptGenerator {
 
     /// Render with optional additional context.
     pub fn render_with_context(&self, question: &str, additional_context: Option<&str>) -> String {
+        self.render_with_prior_extractions(question, additional_context, &[])
+    }
+
+    /// Render a prompt for a follow-up extraction pass that lists what
+    /// earlier passes already extracted from the question text.
+    pub fn render_with_prior_extractions(
+        &self,
+        question: &str,
+        additional_context: Option<&str>,
+        prior_extractions: &[Extraction],
+    ) -> String {
         let mut lines: Vec<String> = Vec::new();
         lines.push(format!("{}\n", self.template.description));
 
@@ -216,6 +232,14 @@ impl QAPromptGenerator {
             }
         }
 
+        if !prior_extractions.is_empty() {
+            lines.push(self.prior_extractions_heading.clone());
+            for extraction in prior_extractions {
+                lines.push(format!("- {}: {}", extraction.extraction_class, extraction.extraction_text));
+            }
+            lines.push(String::new());
+        }
+
         lines.push(format!("{}{}", self.question_prefix, question));
         lines.push(self.answer_prefix.clone());
         lines.join("\n")
@@ -313,4 +337,23 @@ examples:
         assert!(out.contains("Q: What is being asked?"));
         assert!(out.contains("A:"));
     }
+
+    #[test]
+    fn test_render_with_prior_extractions_lists_them_before_question() {
+        let qa_gen = QAPromptGenerator::new(PromptTemplateStructured {
+            description: "Extract people.".to_string(),
+            examples: vec![],
+        });
+        let prior = vec![Extraction {
+            extraction_class: "person".to_string(),
+            extraction_text: "Alice".to_string(),
+            attributes: None,
+        }];
+
+        let out = qa_gen.render_with_prior_extractions("Alice met Bob.", None, &prior);
+        let listed = out.find("- person: Alice").unwrap();
+        assert!(out.contains(&qa_gen.prior_extractions_heading));
+        assert!(listed < out.find("Q: Alice met Bob.").unwrap());
+        assert_eq!(qa_gen.render_with_prior_extractions("Alice met Bob.", None, &[]), qa_gen.render("Alice met Bob."));
+    }
 }
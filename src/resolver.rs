@@ -1,16 +1,25 @@
 // Library for resolving LLM output
 //
 // Notes:
-// - This is a single-file self-contained version including minimal `data` and
-//   `tokenizer` submodules to be runnable out-of-the-box.
+// - This is a single-file self-contained version including a minimal `data`
+//   submodule to be runnable out-of-the-box.
 // - WordAligner implements exact-token-subsequence matching and a sliding-window
-//   fuzzy overlap heuristic (ratio of matched normalized tokens).
-// - Replace tokenizer/tokenization with your production tokenizer for better results.
+//   fuzzy overlap heuristic (ratio of matched normalized tokens). Tokens are
+//   normalized for fuzzy matching by a configurable `crate::normalization`
+//   `TokenNormalizer`.
+// - WordAligner tokenizes both the source and the extraction text with
+//   `crate::tokenizer`, so Han characters and punctuation are tokens of their own
+//   and alignments carry character positions.
 
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use thiserror::Error;
 
+use crate::inference::split_reasoning;
+use crate::lenient::{self, ParseRepair};
+use crate::normalization::{NormalizerChain, TokenNormalizer};
+
 /// -----------------------
 /// Minimal supporting types
 /// -----------------------
@@ -44,8 +53,10 @@ pub mod data {
     #[derive(Debug, Clone, PartialEq, Eq)]
     pub enum AlignmentStatus {
         MatchExact,
+        MatchGreater,
         MatchLesser,
         MatchFuzzy,
+        MatchSentence,
     }
 
     #[derive(Debug, Clone)]
@@ -58,6 +69,12 @@ pub mod data {
         pub token_interval: Option<TokenInterval>,
         pub char_interval: Option<CharInterval>,
         pub alignment_status: Option<AlignmentStatus>,
+        /// Every occurrence of the text was already taken by an earlier
+        /// extraction of the same class, whose span this one shares.
+        pub duplicate: bool,
+        /// Levenshtein distance between the extraction text and the source
+        /// text it was aligned to by character-level fuzzy matching.
+        pub edit_distance: Option<usize>,
     }
 
     impl Extraction {
@@ -77,72 +94,21 @@ pub mod data {
                 token_interval: None,
                 char_interval: None,
                 alignment_status: None,
+                duplicate: false,
+                edit_distance: None,
             }
         }
     }
 }
 
-/// Very small whitespace tokenizer that also returns character spans for tokens.
-/// Replace this with your real tokenizer for production use.
-pub mod tokenizer {
-    use super::data::CharInterval;
-
-    #[derive(Debug, Clone)]
-    pub struct Token {
-        pub text: String,
-        pub char_interval: CharInterval,
-    }
-
-    #[derive(Debug, Clone)]
-    pub struct TokenizedText {
-        pub text: String,
-        pub tokens: Vec<Token>,
-    }
-
-    /// Naive whitespace tokenizer that yields tokens and their char spans.
-    pub fn tokenize(text: &str) -> TokenizedText {
-        let mut tokens = Vec::new();
-        let mut chars = text.char_indices().peekable();
-
-        while let Some((start_idx, ch)) = chars.next() {
-            if ch.is_whitespace() {
-                continue;
-            }
-
-            let mut end_idx = start_idx + ch.len_utf8();
-            let mut token_text = String::new();
-            token_text.push(ch);
-
-            // Collect non-whitespace characters
-            while let Some(&(next_idx, next_ch)) = chars.peek() {
-                if next_ch.is_whitespace() {
-                    break;
-                }
-                token_text.push(next_ch);
-                end_idx = next_idx + next_ch.len_utf8();
-                chars.next();
-            }
-
-            tokens.push(Token {
-                text: token_text,
-                char_interval: CharInterval {
-                    start_pos: start_idx,
-                    end_pos: end_idx,
-                },
-            });
-        }
-
-        TokenizedText {
-            text: text.to_string(),
-            tokens,
-        }
-    }
-}
-
 /// ----------------------------
 /// Resolver implementation
 /// ----------------------------
-const FUZZY_ALIGNMENT_MIN_THRESHOLD: f64 = 0.75;
+pub const FUZZY_ALIGNMENT_MIN_THRESHOLD: f64 = 0.75;
+
+/// Fewest characters of a source token an extraction token must cover to
+/// count as part of a `MatchGreater`, so "he" does not match inside "The".
+pub const MATCH_GREATER_MIN_CHARS: usize = 3;
 
 #[derive(Debug, Error)]
 pub enum ResolverError {
@@ -161,9 +127,21 @@ pub enum ResolverError {
 pub type ResolverResult<T> = Result<T, ResolverError>;
 
 /// AbstractResolver trait (mirrors abstract base class behavior).
-pub trait AbstractResolver {
+pub trait AbstractResolver: Send + Sync {
     fn resolve(&self, input_text: &str, suppress_parse_errors: bool) -> ResolverResult<Vec<data::Extraction>>;
 
+    /// Like `resolve`, also returning the repairs applied to the input to make
+    /// it parse. Resolvers without lenient parsing apply none.
+    fn resolve_with_repairs(
+        &self,
+        input_text: &str,
+        suppress_parse_errors: bool,
+    ) -> ResolverResult<(Vec<data::Extraction>, Vec<ParseRepair>)> {
+        self.resolve(input_text, suppress_parse_errors)
+            .map(|extractions| (extractions, Vec::new()))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn align(
         &self,
         extractions: &[data::Extraction],
@@ -183,6 +161,18 @@ pub struct Resolver {
     pub extraction_index_suffix: Option<String>,
     pub extraction_attributes_suffix: Option<String>,
     pub format_is_yaml: bool,
+    /// Repair malformed output that strict parsing rejects.
+    pub lenient_parsing: bool,
+    /// Normalizes tokens before every alignment tier compares them; `None`
+    /// compares lowercased tokens and case folds and stems them only for fuzzy
+    /// alignment.
+    pub token_normalizer: Option<Arc<dyn TokenNormalizer>>,
+    /// Minimum similarity for attributing an extraction that no strategy
+    /// aligned to its most similar sentence; `None` disables the fallback.
+    pub sentence_fallback: Option<f64>,
+    /// Aligns extractions that cover only part of their first or last source
+    /// token, reported as `MatchGreater`.
+    pub accept_match_greater: bool,
 }
 
 impl Default for Resolver {
@@ -192,6 +182,10 @@ impl Default for Resolver {
             extraction_index_suffix: Some("_index".to_string()),
             extraction_attributes_suffix: Some("_attributes".to_string()),
             format_is_yaml: false,
+            lenient_parsing: false,
+            token_normalizer: None,
+            sentence_fallback: None,
+            accept_match_greater: false,
         }
     }
 }
@@ -208,6 +202,72 @@ impl Resolver {
             extraction_index_suffix,
             extraction_attributes_suffix,
             format_is_yaml,
+            lenient_parsing: false,
+            token_normalizer: None,
+            sentence_fallback: None,
+            accept_match_greater: false,
+        }
+    }
+
+    /// Falls back to tolerant parsing when strict parsing fails, repairing
+    /// trailing commas, single quotes, comments, surrounding prose and
+    /// truncated output. See [`crate::lenient`].
+    pub fn with_lenient_parsing(mut self, lenient_parsing: bool) -> Self {
+        self.lenient_parsing = lenient_parsing;
+        self
+    }
+
+    /// Sets how tokens are normalized before alignment compares them, for
+    /// example to fold Traditional and Simplified Chinese or to strip
+    /// diacritics. The normalizer applies to every match tier, so tokens equal
+    /// after normalization align as `MatchExact`. By default tokens are only
+    /// lowercased, and fuzzy alignment also stems them with
+    /// [`NormalizerChain::standard`]. See [`crate::normalization`].
+    pub fn with_token_normalizer(mut self, normalizer: impl TokenNormalizer + 'static) -> Self {
+        self.token_normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Attributes extractions that no alignment strategy placed, typically
+    /// paraphrases, to the sentence of the chunk they resemble most, with
+    /// status `MatchSentence`. The similarity is the Dice coefficient of the
+    /// normalized word tokens of the sentence and of the extraction text and
+    /// attribute values; sentences below `min_similarity` are not used.
+    pub fn with_sentence_fallback(mut self, min_similarity: f64) -> Self {
+        self.sentence_fallback = Some(min_similarity);
+        self
+    }
+
+    /// Aligns extractions whose first or last token is a truncated source
+    /// token, as "Bob Sto" within "Bob Stone", with status `MatchGreater`.
+    /// Off by default. The truncated token must keep at least
+    /// [`MATCH_GREATER_MIN_CHARS`] characters.
+    pub fn with_match_greater(mut self, accept_match_greater: bool) -> Self {
+        self.accept_match_greater = accept_match_greater;
+        self
+    }
+
+    /// Parses the content after any `<think>` reasoning, retrying leniently
+    /// when enabled, and returns the repairs that were needed.
+    fn parse_content_with_repairs(&self, input_string: &str) -> ResolverResult<(JsonValue, Vec<ParseRepair>)> {
+        // Reasoning may contain fenced blocks of its own; only the answer is parsed.
+        let (_, answer) = split_reasoning(input_string);
+        let input_string = answer.as_str();
+        match self.extract_and_parse_content(input_string) {
+            Ok(parsed) => Ok((parsed, Vec::new())),
+            Err(e) if !self.lenient_parsing || input_string.trim().is_empty() => Err(e),
+            Err(e) => {
+                let (content, mut repairs) =
+                    lenient::select_content(input_string, self.fence_output, self.format_is_yaml);
+                let parsed = if self.format_is_yaml {
+                    let truncated = repairs.contains(&ParseRepair::UnterminatedFence);
+                    lenient::parse_yaml(&content, truncated).map_err(|_| e)?
+                } else {
+                    lenient::parse_json(&content).map_err(|_| e)?
+                };
+                repairs.extend(parsed.1);
+                Ok((parsed.0, repairs))
+            }
         }
     }
 
@@ -253,8 +313,11 @@ impl Resolver {
 
     /// string_to_extraction_data: ensure mapping with "extractions": [...]
     fn string_to_extraction_data(&self, input_string: &str) -> ResolverResult<Vec<HashMap<String, JsonValue>>> {
-        let parsed = self.extract_and_parse_content(input_string)?;
+        let (parsed, _) = self.parse_content_with_repairs(input_string)?;
+        self.value_to_extraction_data(parsed)
+    }
 
+    fn value_to_extraction_data(&self, parsed: JsonValue) -> ResolverResult<Vec<HashMap<String, JsonValue>>> {
         // Handle simple array format
         if let Some(array) = parsed.as_array() {
             // Simple array format: ["item1", "item2", ...]
@@ -284,61 +347,61 @@ impl Resolver {
                 })?;
 
                 // Check if this is DeepSeek format: [{"characters": "text", "characters_attributes": {}}, ...]
-                if let Some(first_item) = arr.first() {
-                    if let Some(first_obj) = first_item.as_object() {
-                        let mut has_category_fields = false;
-                        for key in first_obj.keys() {
-                            if !key.ends_with("_attributes") && key != "extraction_class" && key != "extraction_text" {
-                                has_category_fields = true;
-                                break;
-                            }
+                if let Some(first_item) = arr.first()
+                    && let Some(first_obj) = first_item.as_object()
+                {
+                    let mut has_category_fields = false;
+                    for key in first_obj.keys() {
+                        if !key.ends_with("_attributes") && key != "extraction_class" && key != "extraction_text" {
+                            has_category_fields = true;
+                            break;
                         }
+                    }
 
-                        if has_category_fields {
-                            // Process DeepSeek format
-                            let mut result = Vec::new();
-                            for item in arr {
-                                if let Some(item_obj) = item.as_object() {
-                                    for (key, value) in item_obj {
-                                        // Skip index and attributes keys
-                                        let should_skip = key.ends_with("_attributes")
-                                            || (self.extraction_index_suffix.is_some()
-                                                && key.ends_with(self.extraction_index_suffix.as_ref().unwrap()));
-
-                                        if !should_skip {
-                                            let mut extraction_map = HashMap::new();
-                                            extraction_map
-                                                .insert("extraction_class".to_string(), JsonValue::String(key.clone()));
-                                            extraction_map.insert("extraction_text".to_string(), value.clone());
-
-                                            // Copy over related index and attributes fields
-                                            if let Some(index_suffix) = &self.extraction_index_suffix {
-                                                let index_key = format!("{}{}", key, index_suffix);
-                                                if let Some(index_value) = item_obj.get(&index_key) {
-                                                    extraction_map.insert(
-                                                        format!("extraction_text{}", index_suffix),
-                                                        index_value.clone(),
-                                                    );
-                                                }
+                    if has_category_fields {
+                        // Process DeepSeek format
+                        let mut result = Vec::new();
+                        for item in arr {
+                            if let Some(item_obj) = item.as_object() {
+                                for (key, value) in item_obj {
+                                    // Skip index and attributes keys
+                                    let should_skip = key.ends_with("_attributes")
+                                        || (self.extraction_index_suffix.is_some()
+                                            && key.ends_with(self.extraction_index_suffix.as_ref().unwrap()));
+
+                                    if !should_skip {
+                                        let mut extraction_map = HashMap::new();
+                                        extraction_map
+                                            .insert("extraction_class".to_string(), JsonValue::String(key.clone()));
+                                        extraction_map.insert("extraction_text".to_string(), value.clone());
+
+                                        // Copy over related index and attributes fields
+                                        if let Some(index_suffix) = &self.extraction_index_suffix {
+                                            let index_key = format!("{}{}", key, index_suffix);
+                                            if let Some(index_value) = item_obj.get(&index_key) {
+                                                extraction_map.insert(
+                                                    format!("extraction_text{}", index_suffix),
+                                                    index_value.clone(),
+                                                );
                                             }
+                                        }
 
-                                            if let Some(attr_suffix) = &self.extraction_attributes_suffix {
-                                                let attr_key = format!("{}{}", key, attr_suffix);
-                                                if let Some(attr_value) = item_obj.get(&attr_key) {
-                                                    extraction_map.insert(
-                                                        format!("extraction_text{}", attr_suffix),
-                                                        attr_value.clone(),
-                                                    );
-                                                }
+                                        if let Some(attr_suffix) = &self.extraction_attributes_suffix {
+                                            let attr_key = format!("{}{}", key, attr_suffix);
+                                            if let Some(attr_value) = item_obj.get(&attr_key) {
+                                                extraction_map.insert(
+                                                    format!("extraction_text{}", attr_suffix),
+                                                    attr_value.clone(),
+                                                );
                                             }
-
-                                            result.push(extraction_map);
                                         }
+
+                                        result.push(extraction_map);
                                     }
                                 }
                             }
-                            return Ok(result);
                         }
+                        return Ok(result);
                     }
                 }
 
@@ -540,11 +603,23 @@ impl Resolver {
 
 impl AbstractResolver for Resolver {
     fn resolve(&self, input_text: &str, suppress_parse_errors: bool) -> ResolverResult<Vec<data::Extraction>> {
-        match self.string_to_extraction_data(input_text) {
-            Ok(parsed) => self.extract_ordered_extractions_impl(&parsed),
+        self.resolve_with_repairs(input_text, suppress_parse_errors)
+            .map(|(extractions, _)| extractions)
+    }
+
+    fn resolve_with_repairs(
+        &self,
+        input_text: &str,
+        suppress_parse_errors: bool,
+    ) -> ResolverResult<(Vec<data::Extraction>, Vec<ParseRepair>)> {
+        let parsed = self
+            .parse_content_with_repairs(input_text)
+            .and_then(|(parsed, repairs)| Ok((self.value_to_extraction_data(parsed)?, repairs)));
+        match parsed {
+            Ok((parsed, repairs)) => Ok((self.extract_ordered_extractions_impl(&parsed)?, repairs)),
             Err(e) => {
                 if suppress_parse_errors {
-                    Ok(Vec::new())
+                    Ok((Vec::new(), Vec::new()))
                 } else {
                     Err(e)
                 }
@@ -567,7 +642,14 @@ impl AbstractResolver for Resolver {
         }
 
         let groups = vec![extractions.to_vec()];
-        let mut aligner = WordAligner::new();
+        let aligner = match &self.token_normalizer {
+            Some(normalizer) => WordAligner::with_normalizer(normalizer.clone()),
+            None => WordAligner::new(),
+        };
+        let mut aligner = aligner.with_match_greater(self.accept_match_greater);
+        if let Some(min_similarity) = self.sentence_fallback {
+            aligner = aligner.with_sentence_fallback(min_similarity);
+        }
         let char_offset_val = char_offset.unwrap_or(0);
 
         let aligned = aligner.align_extractions(
@@ -587,13 +669,55 @@ impl AbstractResolver for Resolver {
 /// ----------------------------
 /// WordAligner (exact + fuzzy)
 /// ----------------------------
-pub struct WordAligner;
+pub struct WordAligner {
+    normalizer: Arc<dyn TokenNormalizer>,
+    normalize_all_tiers: bool,
+    sentence_fallback: Option<f64>,
+    accept_match_greater: bool,
+}
 
 impl WordAligner {
+    /// An aligner that compares lowercased tokens, case folding and stemming
+    /// them with [`NormalizerChain::standard`] only for fuzzy and sentence
+    /// matching.
     pub fn new() -> Self {
-        Self
+        Self {
+            normalize_all_tiers: false,
+            ..Self::with_normalizer(NormalizerChain::standard())
+        }
     }
 
+    /// An aligner that compares tokens after `normalizer` in every match tier.
+    pub fn with_normalizer(normalizer: impl TokenNormalizer + 'static) -> Self {
+        Self {
+            normalizer: Arc::new(normalizer),
+            normalize_all_tiers: true,
+            sentence_fallback: None,
+            accept_match_greater: false,
+        }
+    }
+
+    /// Falls back to the most similar sentence, if at least `min_similarity`
+    /// similar, for extractions no other strategy aligns.
+    pub fn with_sentence_fallback(mut self, min_similarity: f64) -> Self {
+        self.sentence_fallback = Some(min_similarity);
+        self
+    }
+
+    /// Accepts extractions whose first or last token is a truncated source
+    /// token (`MatchGreater`).
+    pub fn with_match_greater(mut self, accept_match_greater: bool) -> Self {
+        self.accept_match_greater = accept_match_greater;
+        self
+    }
+
+    /// Aligns every extraction of every group to `source_text`.
+    ///
+    /// Extractions are aligned in group order and, within a group, in
+    /// `extraction_index` order, each repeated mention taking the next free
+    /// occurrence of its text. A mention left without a free occurrence is
+    /// aligned to the first one and flagged as a duplicate.
+    #[allow(clippy::too_many_arguments)]
     pub fn align_extractions(
         &mut self,
         extraction_groups: &[Vec<data::Extraction>],
@@ -602,155 +726,409 @@ impl WordAligner {
         char_offset: usize,
         enable_fuzzy_alignment: bool,
         fuzzy_alignment_threshold: f64,
-        _accept_match_lesser: bool,
+        accept_match_lesser: bool,
     ) -> Vec<Vec<data::Extraction>> {
-        let source_tokenized = tokenizer::tokenize(source_text);
-        let source_tokens: Vec<String> = source_tokenized.tokens.iter().map(|t| t.text.to_lowercase()).collect();
-
-        let mut aligned_groups = vec![Vec::new(); extraction_groups.len()];
-
-        for (g_idx, group) in extraction_groups.iter().enumerate() {
-            for extraction in group {
-                let aligned_extraction = self.align_single_extraction(
-                    extraction,
+        let source_tokenized = crate::tokenizer::tokenize(source_text);
+        let source_tokens = token_texts(&source_tokenized);
+        let match_tokens = self.match_tokens(&source_tokens);
+        let mut occurrences = OccurrenceTracker::default();
+
+        let mut aligned_groups = Vec::with_capacity(extraction_groups.len());
+
+        for group in extraction_groups {
+            let mut order: Vec<usize> = (0..group.len()).collect();
+            order.sort_by_key(|&i| group[i].extraction_index);
+            let mut aligned_group = group.clone();
+            for i in order {
+                aligned_group[i] = self.align_single_extraction(
+                    &group[i],
                     &source_tokens,
+                    &match_tokens,
                     &source_tokenized,
                     token_offset,
                     char_offset,
                     enable_fuzzy_alignment,
                     fuzzy_alignment_threshold,
+                    accept_match_lesser,
+                    &mut occurrences,
                 );
-                aligned_groups[g_idx].push(aligned_extraction);
             }
+            aligned_groups.push(aligned_group);
         }
 
         aligned_groups
     }
 
+    /// Aligns one extraction, trying in order an exact match, a match inside
+    /// longer source tokens (`MatchGreater`, when enabled), then, when fuzzy
+    /// alignment is enabled, a token overlap match and a character-level
+    /// edit-distance match (both `MatchFuzzy`), and a match of only part of
+    /// the extraction (`MatchLesser`, when `accept_match_lesser`). Fuzzy
+    /// matches come first because they cover the whole extraction. With the
+    /// sentence fallback, an extraction still unaligned is attributed to the
+    /// most similar sentence (`MatchSentence`).
+    #[allow(clippy::too_many_arguments)]
     fn align_single_extraction(
         &self,
         extraction: &data::Extraction,
         source_tokens: &[String],
-        source_tokenized: &tokenizer::TokenizedText,
+        match_tokens: &[String],
+        source_tokenized: &crate::tokenizer::TokenizedText,
         token_offset: usize,
         char_offset: usize,
         enable_fuzzy_alignment: bool,
         fuzzy_alignment_threshold: f64,
+        accept_match_lesser: bool,
+        occurrences: &mut OccurrenceTracker,
     ) -> data::Extraction {
-        let ext_tokens: Vec<String> = extraction
-            .extraction_text
-            .split_whitespace()
-            .map(|s| s.to_lowercase())
-            .collect();
+        let ext_tokens = token_texts(&crate::tokenizer::tokenize(&extraction.extraction_text));
 
         if ext_tokens.is_empty() {
             return extraction.clone();
         }
+        let class = extraction.extraction_class.as_str();
+        let length = ext_tokens.len();
+        let ext_match_tokens = self.match_tokens(&ext_tokens);
 
-        // Try exact match first
-        if let Some(match_pos) = self.find_exact_match(&ext_tokens, source_tokens) {
-            return self.create_aligned_extraction(
-                extraction,
-                match_pos,
-                ext_tokens.len(),
-                source_tokenized,
-                token_offset,
-                char_offset,
-                data::AlignmentStatus::MatchExact,
-            );
+        let mut edit_distance = None;
+        let found = if let Some((start, duplicate)) =
+            occurrences.take(class, &self.find_exact_matches(&ext_match_tokens, match_tokens), length)
+        {
+            Some((start, length, data::AlignmentStatus::MatchExact, duplicate))
+        } else if let Some((start, duplicate)) = self
+            .accept_match_greater
+            .then(|| {
+                occurrences.take(
+                    class,
+                    &self.find_greater_matches(&ext_match_tokens, match_tokens),
+                    length,
+                )
+            })
+            .flatten()
+        {
+            Some((start, length, data::AlignmentStatus::MatchGreater, duplicate))
+        } else if let Some((start, length)) = enable_fuzzy_alignment
+            .then(|| self.find_fuzzy_match(&ext_tokens, source_tokens, fuzzy_alignment_threshold))
+            .flatten()
+        {
+            let duplicate = occurrences
+                .take(class, &[start], length)
+                .is_some_and(|(_, duplicate)| duplicate);
+            Some((start, length, data::AlignmentStatus::MatchFuzzy, duplicate))
+        } else if let Some((start, length, distance)) = enable_fuzzy_alignment
+            .then(|| {
+                self.find_edit_distance_match(
+                    &extraction.extraction_text,
+                    source_tokenized,
+                    1.0 - fuzzy_alignment_threshold,
+                )
+            })
+            .flatten()
+        {
+            edit_distance = Some(distance);
+            let duplicate = occurrences
+                .take(class, &[start], length)
+                .is_some_and(|(_, duplicate)| duplicate);
+            Some((start, length, data::AlignmentStatus::MatchFuzzy, duplicate))
+        } else if let Some((start, length)) = accept_match_lesser
+            .then(|| self.find_lesser_match(&ext_match_tokens, match_tokens))
+            .flatten()
+        {
+            let duplicate = occurrences
+                .take(class, &[start], length)
+                .is_some_and(|(_, duplicate)| duplicate);
+            Some((start, length, data::AlignmentStatus::MatchLesser, duplicate))
+        } else if let Some((start, length)) = self.sentence_fallback.and_then(|min_similarity| {
+            self.find_sentence_match(extraction, source_tokens, source_tokenized, min_similarity)
+        }) {
+            Some((start, length, data::AlignmentStatus::MatchSentence, false))
+        } else {
+            None
+        };
+
+        let Some((start, length, status, duplicate)) = found else {
+            // No alignment found
+            return extraction.clone();
+        };
+        let mut aligned = self.create_aligned_extraction(
+            extraction,
+            start,
+            length,
+            source_tokenized,
+            token_offset,
+            char_offset,
+            status,
+        );
+        aligned.duplicate = duplicate;
+        aligned.edit_distance = edit_distance;
+        aligned
+    }
+
+    /// The forms of `tokens` that the exact, greater and lesser tiers compare:
+    /// normalized when the normalizer applies to every tier, else unchanged.
+    fn match_tokens(&self, tokens: &[String]) -> Vec<String> {
+        if self.normalize_all_tiers {
+            tokens.iter().map(|token| self.normalizer.normalize(token)).collect()
+        } else {
+            tokens.to_vec()
         }
+    }
 
-        // Try fuzzy match if enabled
-        if enable_fuzzy_alignment
-            && let Some((start_idx, window_size)) =
-                self.find_fuzzy_match(&ext_tokens, source_tokens, fuzzy_alignment_threshold)
-        {
-            return self.create_aligned_extraction(
-                extraction,
-                start_idx,
-                window_size,
-                source_tokenized,
-                token_offset,
-                char_offset,
-                data::AlignmentStatus::MatchFuzzy,
-            );
+    /// Start positions of every occurrence of `needle` in `haystack`.
+    fn find_exact_matches(&self, needle: &[String], haystack: &[String]) -> Vec<usize> {
+        if needle.is_empty() || haystack.len() < needle.len() {
+            return Vec::new();
         }
 
-        // No alignment found
-        extraction.clone()
+        (0..=(haystack.len() - needle.len()))
+            .filter(|&start| &haystack[start..start + needle.len()] == needle)
+            .collect()
     }
 
-    fn find_exact_match(&self, needle: &[String], haystack: &[String]) -> Option<usize> {
-        if needle.is_empty() || haystack.len() < needle.len() {
-            return None;
+    /// Finds source tokens that contain the extraction but are longer: the
+    /// first extraction token ends a source token, the last one starts one and
+    /// those in between are equal, as "Bob Sto" is contained in "Bob Stone".
+    /// A truncated token must keep at least [`MATCH_GREATER_MIN_CHARS`]
+    /// characters. Returns the start position of every such span.
+    fn find_greater_matches(&self, needle: &[String], haystack: &[String]) -> Vec<usize> {
+        let n = needle.len();
+        if n == 0 || haystack.len() < n {
+            return Vec::new();
         }
+        let partial = |source: &str, ext: &str, fits: fn(&str, &str) -> bool| {
+            source == ext || (ext.chars().count() >= MATCH_GREATER_MIN_CHARS && fits(source, ext))
+        };
+        (0..=(haystack.len() - n))
+            .filter(|&start| {
+                let window = &haystack[start..start + n];
+                if n == 1 {
+                    return partial(&window[0], &needle[0], |s, e| s.starts_with(e) || s.ends_with(e));
+                }
+                partial(&window[0], &needle[0], |s, e| s.ends_with(e))
+                    && partial(&window[n - 1], &needle[n - 1], |s, e| s.starts_with(e))
+                    && window[1..n - 1] == needle[1..n - 1]
+            })
+            .collect()
+    }
 
-        (0..=(haystack.len() - needle.len())).find(|&start| &haystack[start..start + needle.len()] == needle)
+    /// Finds the longest run of consecutive extraction tokens that appears in
+    /// the source, returning its source start and length, when the run is at
+    /// least half of the extraction.
+    fn find_lesser_match(&self, needle: &[String], haystack: &[String]) -> Option<(usize, usize)> {
+        let mut best_len = 0;
+        let mut best_end = 0;
+        let mut previous = vec![0usize; needle.len() + 1];
+        for (j, source_token) in haystack.iter().enumerate() {
+            let mut current = vec![0usize; needle.len() + 1];
+            for (i, ext_token) in needle.iter().enumerate() {
+                if ext_token == source_token {
+                    current[i + 1] = previous[i] + 1;
+                    if current[i + 1] > best_len {
+                        best_len = current[i + 1];
+                        best_end = j + 1;
+                    }
+                }
+            }
+            previous = current;
+        }
+        (best_len > 0 && best_len * 2 >= needle.len()).then(|| (best_end - best_len, best_len))
     }
 
+    /// Finds the source span whose tokens cover the largest share of the
+    /// extraction tokens, at least `threshold` of them.
+    ///
+    /// Windows range from the extraction length up to the length at which
+    /// `threshold` of the window could still be extraction tokens; each size
+    /// slides over the source once, updating the token counts and the overlap
+    /// incrementally. Among equally good spans the shortest, then the
+    /// leftmost, wins.
     fn find_fuzzy_match(
         &self,
         ext_tokens: &[String],
         source_tokens: &[String],
         threshold: f64,
     ) -> Option<(usize, usize)> {
-        let ext_norm: Vec<String> = ext_tokens.iter().map(|t| normalize_token(t)).collect();
-        let mut ext_counts = HashMap::new();
-        for token in &ext_norm {
-            *ext_counts.entry(token.clone()).or_insert(0usize) += 1;
+        let ext_len = ext_tokens.len();
+        if ext_len == 0 || source_tokens.len() < ext_len {
+            return None;
+        }
+        let mut ext_counts: HashMap<String, usize> = HashMap::new();
+        for token in ext_tokens {
+            *ext_counts.entry(self.normalizer.normalize(token)).or_insert(0) += 1;
         }
+        let source_norm: Vec<String> = source_tokens.iter().map(|t| self.normalizer.normalize(t)).collect();
 
-        let min_overlap = (ext_norm.len() as f64 * threshold).ceil() as usize;
-        let mut best_ratio = 0.0f64;
+        let min_overlap = (ext_len as f64 * threshold).ceil() as usize;
+        let max_window = if threshold > 0.0 {
+            ((ext_len as f64 / threshold).ceil() as usize).clamp(ext_len, source_norm.len())
+        } else {
+            source_norm.len()
+        };
+        let mut best_overlap = 0;
         let mut best_span = None;
 
-        // Try different window sizes
-        for window_size in ext_norm.len()..=source_tokens.len() {
-            if window_size > source_tokens.len() {
-                break;
-            }
-
-            for start_idx in 0..=source_tokens.len() - window_size {
-                let window: Vec<String> = source_tokens[start_idx..start_idx + window_size]
-                    .iter()
-                    .map(|t| normalize_token(t))
-                    .collect();
-
-                let overlap = self.calculate_overlap(&ext_counts, &window);
-                if overlap >= min_overlap {
-                    let ratio = overlap as f64 / ext_norm.len() as f64;
-                    if ratio > best_ratio {
-                        best_ratio = ratio;
-                        best_span = Some((start_idx, window_size));
+        for window_size in ext_len..=max_window {
+            let mut window_counts: HashMap<&str, usize> = HashMap::new();
+            let mut overlap = 0;
+            for (end, token) in source_norm.iter().enumerate() {
+                let wanted = ext_counts.get(token).copied().unwrap_or(0);
+                let count = window_counts.entry(token).or_insert(0);
+                if *count < wanted {
+                    overlap += 1;
+                }
+                *count += 1;
+
+                if end >= window_size {
+                    let leaving = source_norm[end - window_size].as_str();
+                    let wanted = ext_counts.get(leaving).copied().unwrap_or(0);
+                    let count = window_counts.entry(leaving).or_insert(0);
+                    if *count <= wanted {
+                        overlap -= 1;
                     }
+                    *count -= 1;
+                }
+                if end + 1 < window_size || overlap < min_overlap || overlap <= best_overlap {
+                    continue;
+                }
+                best_overlap = overlap;
+                best_span = Some((end + 1 - window_size, window_size));
+                if best_overlap == ext_len {
+                    return best_span;
                 }
             }
         }
 
-        if best_ratio >= threshold { best_span } else { None }
+        best_span
     }
 
-    fn calculate_overlap(&self, ext_counts: &HashMap<String, usize>, window_tokens: &[String]) -> usize {
-        let mut window_counts = HashMap::new();
-        for token in window_tokens {
-            *window_counts.entry(token.clone()).or_insert(0usize) += 1;
+    /// Finds the sentence most similar to the extraction, at least
+    /// `min_similarity` similar, returning its tokens as start and length.
+    ///
+    /// Similarity is the Dice coefficient between the normalized word and
+    /// number tokens of the sentence and those of the extraction text and its
+    /// attribute values, which often name what a paraphrase leaves out. Ties
+    /// go to the earliest sentence.
+    fn find_sentence_match(
+        &self,
+        extraction: &data::Extraction,
+        source_tokens: &[String],
+        source_tokenized: &crate::tokenizer::TokenizedText,
+        min_similarity: f64,
+    ) -> Option<(usize, usize)> {
+        let mut text = extraction.extraction_text.clone();
+        if let Some(attributes) = &extraction.attributes {
+            collect_strings(attributes, &mut text);
+        }
+        let ext_tokenized = crate::tokenizer::tokenize(&text);
+        let ext_counts = self.word_counts(&ext_tokenized.tokens, &token_texts(&ext_tokenized));
+        let ext_total: usize = ext_counts.values().sum();
+        if ext_total == 0 {
+            return None;
         }
 
-        ext_counts
-            .iter()
-            .map(|(token, &ext_count)| {
-                let window_count = window_counts.get(token).copied().unwrap_or(0);
-                std::cmp::min(ext_count, window_count)
-            })
-            .sum()
+        let tokens = &source_tokenized.tokens;
+        let mut best: Option<(f64, usize, usize)> = None;
+        let mut start = 0;
+        while start < tokens.len() {
+            let Ok(range) = crate::tokenizer::find_sentence_range(&source_tokenized.text, tokens, start) else {
+                break;
+            };
+            let sentence = range.start_index..range.end_index;
+            let sentence_counts = self.word_counts(&tokens[sentence.clone()], &source_tokens[sentence]);
+            let sentence_total: usize = sentence_counts.values().sum();
+            let shared: usize = ext_counts
+                .iter()
+                .map(|(token, count)| (*count).min(sentence_counts.get(token).copied().unwrap_or(0)))
+                .sum();
+            let similarity = 2.0 * shared as f64 / (ext_total + sentence_total) as f64;
+            if shared > 0 && similarity >= min_similarity && best.is_none_or(|(best, _, _)| similarity > best) {
+                best = Some((similarity, range.start_index, range.end_index - range.start_index));
+            }
+            start = range.end_index.max(start + 1);
+        }
+        best.map(|(_, start, length)| (start, length))
     }
 
+    /// Counts the normalized forms of the word and number tokens among
+    /// `tokens`, whose lowercased texts are `texts`.
+    fn word_counts(&self, tokens: &[crate::tokenizer::Token], texts: &[String]) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for (token, text) in tokens.iter().zip(texts) {
+            if token.token_type != crate::tokenizer::TokenType::Punctuation {
+                *counts.entry(self.normalizer.normalize(text)).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Finds the source substring closest to `text` in Levenshtein distance,
+    /// for text the model corrected, such as OCR typos or words hyphenated
+    /// across line breaks.
+    ///
+    /// Both texts are compared lowercased, with whitespace runs collapsed and
+    /// line-break hyphenation removed. The distance may be at most
+    /// `max_distance_ratio` of the length of `text`. Returns the tokens
+    /// covering the substring, as start and length, and the distance.
+    fn find_edit_distance_match(
+        &self,
+        text: &str,
+        source_tokenized: &crate::tokenizer::TokenizedText,
+        max_distance_ratio: f64,
+    ) -> Option<(usize, usize, usize)> {
+        let (pattern, _) = normalize_for_edit_distance(text);
+        let (source, positions) = normalize_for_edit_distance(&source_tokenized.text);
+        let max_distance = (pattern.len() as f64 * max_distance_ratio).floor() as usize;
+        if pattern.is_empty() || max_distance == 0 {
+            return None;
+        }
+
+        // Approximate substring matching: row 0 is free so a match may start
+        // anywhere; each cell tracks where its best alignment started.
+        let m = pattern.len();
+        let mut previous: Vec<usize> = (0..=m).collect();
+        let mut previous_start = vec![0; m + 1];
+        // (distance, start, end) in normalized source chars.
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (j, &c) in source.iter().enumerate() {
+            let mut current = vec![0; m + 1];
+            let mut current_start = vec![j + 1; m + 1];
+            for i in 1..=m {
+                let substitute = previous[i - 1] + usize::from(pattern[i - 1] != c);
+                let skip_source = previous[i] + 1;
+                let skip_pattern = current[i - 1] + 1;
+                (current[i], current_start[i]) = if substitute <= skip_source && substitute <= skip_pattern {
+                    (substitute, previous_start[i - 1])
+                } else if skip_source <= skip_pattern {
+                    (skip_source, previous_start[i])
+                } else {
+                    (skip_pattern, current_start[i - 1])
+                };
+            }
+            if current[m] <= max_distance && best.is_none_or(|(distance, _, _)| current[m] < distance) {
+                best = Some((current[m], current_start[m], j + 1));
+            }
+            previous = current;
+            previous_start = current_start;
+        }
+
+        let (distance, start, end) = best?;
+        if start >= end {
+            return None;
+        }
+        let (char_start, char_end) = (positions[start], positions[end - 1] + 1);
+        let tokens = &source_tokenized.tokens;
+        let first = tokens.iter().position(|t| t.char_interval.end_pos > char_start)?;
+        let last = tokens.iter().rposition(|t| t.char_interval.start_pos < char_end)?;
+        (first <= last).then_some((first, last + 1 - first, distance))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn create_aligned_extraction(
         &self,
         extraction: &data::Extraction,
         start_idx: usize,
         length: usize,
-        source_tokenized: &tokenizer::TokenizedText,
+        source_tokenized: &crate::tokenizer::TokenizedText,
         token_offset: usize,
         char_offset: usize,
         status: data::AlignmentStatus,
@@ -782,13 +1160,106 @@ impl Default for WordAligner {
     }
 }
 
-/// Lowercase + light plural stemming (remove trailing 's' if >3 chars and not 'ss')
-fn normalize_token(tok: &str) -> String {
-    let mut s = tok.to_lowercase();
-    if s.len() > 3 && s.ends_with('s') && !s.ends_with("ss") {
-        s.pop();
+/// Source spans already taken while aligning, so that repeated mentions of
+/// the same text map to successive occurrences.
+#[derive(Default)]
+struct OccurrenceTracker {
+    /// Token just after the last span taken.
+    cursor: usize,
+    /// Taken spans as (class, start, length).
+    taken: HashSet<(String, usize, usize)>,
+}
+
+impl OccurrenceTracker {
+    /// Takes one of the occurrences starting at `starts` for an extraction of
+    /// `class`: the first free one at or after the cursor, else the first free
+    /// one before it. When all are taken, the first is shared and reported as
+    /// a duplicate. Returns the start and whether it is a duplicate.
+    fn take(&mut self, class: &str, starts: &[usize], length: usize) -> Option<(usize, bool)> {
+        let is_free = |start: usize| !self.taken.contains(&(class.to_string(), start, length));
+        let free = starts
+            .iter()
+            .copied()
+            .find(|&start| start >= self.cursor && is_free(start))
+            .or_else(|| starts.iter().copied().find(|&start| is_free(start)));
+        let Some(start) = free else {
+            return starts.first().map(|&start| (start, true));
+        };
+        self.taken.insert((class.to_string(), start, length));
+        self.cursor = start + length;
+        Some((start, false))
+    }
+}
+
+/// Lowercased text of every token, in order.
+fn token_texts(tokenized: &crate::tokenizer::TokenizedText) -> Vec<String> {
+    let chars: Vec<char> = tokenized.text.chars().collect();
+    tokenized
+        .tokens
+        .iter()
+        .map(|token| {
+            chars[token.char_interval.start_pos..token.char_interval.end_pos]
+                .iter()
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect()
+}
+
+/// Appends every string in `value`, at any depth, to `text`.
+fn collect_strings(value: &JsonValue, text: &mut String) {
+    match value {
+        JsonValue::String(s) => {
+            text.push(' ');
+            text.push_str(s);
+        }
+        JsonValue::Array(items) => items.iter().for_each(|item| collect_strings(item, text)),
+        JsonValue::Object(map) => map.values().for_each(|item| collect_strings(item, text)),
+        _ => {}
     }
-    s
+}
+
+/// Lowercases `text`, collapses whitespace runs to a space and joins words
+/// hyphenated across a line break, returning the characters and, for each,
+/// its character position in `text`.
+fn normalize_for_edit_distance(text: &str) -> (Vec<char>, Vec<usize>) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut normalized = Vec::with_capacity(chars.len());
+    let mut positions = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            let run_start = i;
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            if !normalized.is_empty() && i < chars.len() {
+                normalized.push(' ');
+                positions.push(run_start);
+            }
+            continue;
+        }
+        if c == '-' {
+            let mut next = i + 1;
+            while next < chars.len() && chars[next].is_whitespace() && chars[next] != '\n' {
+                next += 1;
+            }
+            if next < chars.len() && chars[next] == '\n' {
+                i = next;
+                while i < chars.len() && chars[i].is_whitespace() {
+                    i += 1;
+                }
+                continue;
+            }
+        }
+        for lower in c.to_lowercase() {
+            normalized.push(lower);
+            positions.push(i);
+        }
+        i += 1;
+    }
+    (normalized, positions)
 }
 
 /// ----------------------------
@@ -872,16 +1343,323 @@ mod tests {
         let source = "the race involved many runners and running race participants";
         let aligned = resolver.align(&[ex], source, 0, Some(0), true, 0.3, true);
         assert_eq!(aligned.len(), 1);
-        // Test passes if no panic occurs
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchFuzzy));
+        let tokens = aligned[0].token_interval.as_ref().unwrap();
+        assert_eq!((tokens.start_index, tokens.end_index), (6, 8));
+        let chars = aligned[0].char_interval.as_ref().unwrap();
+        assert_eq!((chars.start_pos, chars.end_pos), (35, 47));
+
+        // The best span is longer than the extraction when other words sit
+        // between its tokens.
+        let ex = data::Extraction::new("symptom".to_string(), "severe chest pain".to_string(), 1, 0, None);
+        let source = "patient reports chest severe and sharp pain today";
+        let aligned = resolver.align(&[ex], source, 0, Some(0), true, 0.6, false);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchFuzzy));
+        let tokens = aligned[0].token_interval.as_ref().unwrap();
+        assert_eq!((tokens.start_index, tokens.end_index), (2, 7));
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "chest severe and sharp pain"
+        );
+    }
+
+    #[test]
+    fn test_alignment_greater_and_lesser() {
+        let resolver = Resolver::new(false, None, None, false).with_match_greater(true);
+        let source = "Alice met Bob Stone at the harbour today.";
+        let greater = data::Extraction::new("person".to_string(), "Bob Sto".to_string(), 1, 0, None);
+        let lesser = data::Extraction::new("place".to_string(), "the harbour of Dover".to_string(), 2, 0, None);
+        let unrelated = data::Extraction::new("place".to_string(), "the city of Dover".to_string(), 3, 0, None);
+        let extractions = [greater, lesser, unrelated];
+
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, true);
+        let statuses: Vec<_> = aligned.iter().map(|e| e.alignment_status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                Some(data::AlignmentStatus::MatchGreater),
+                Some(data::AlignmentStatus::MatchLesser),
+                None,
+            ]
+        );
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "Bob Stone"
+        );
+        assert_eq!(
+            char_slice(source, aligned[1].char_interval.as_ref().unwrap()),
+            "the harbour"
+        );
+
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, false);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchGreater));
+        assert_eq!(aligned[1].alignment_status, None);
+        assert!(aligned[1].char_interval.is_none());
+
+        // MatchGreater is opt-in.
+        let aligned =
+            Resolver::new(false, None, None, false).align(&extractions, source, 0, Some(0), false, 0.75, false);
+        assert_eq!(aligned[0].alignment_status, None);
+    }
+
+    #[test]
+    fn test_alignment_greater_rejects_short_fragments() {
+        let resolver = Resolver::new(false, None, None, false).with_match_greater(true);
+        let source = "The nurse met Alice in the hall.";
+        let extractions = [
+            data::Extraction::new("person".to_string(), "he".to_string(), 1, 0, None),
+            data::Extraction::new("person".to_string(), "a".to_string(), 2, 0, None),
+            data::Extraction::new("person".to_string(), "se met Al".to_string(), 3, 0, None),
+            data::Extraction::new("person".to_string(), "ice".to_string(), 4, 0, None),
+        ];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, false);
+        let statuses: Vec<_> = aligned.iter().map(|e| e.alignment_status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![None, None, None, Some(data::AlignmentStatus::MatchGreater)]
+        );
+        assert_eq!(char_slice(source, aligned[3].char_interval.as_ref().unwrap()), "Alice");
+    }
+
+    #[test]
+    fn test_alignment_consumes_repeated_mentions() {
+        let resolver = Resolver::new(false, None, None, false);
+        let source = "Take aspirin daily. Stop aspirin before surgery. Resume aspirin after.";
+        let mention = |index| data::Extraction::new("drug".to_string(), "aspirin".to_string(), index, 0, None);
+        let extractions = [mention(2), mention(1), mention(3), mention(4)];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, false);
+
+        let starts: Vec<_> = aligned
+            .iter()
+            .map(|e| e.char_interval.as_ref().unwrap().start_pos)
+            .collect();
+        assert_eq!(starts, vec![25, 5, 56, 5]);
+        let duplicates: Vec<_> = aligned.iter().map(|e| e.duplicate).collect();
+        assert_eq!(duplicates, vec![false, false, false, true]);
+
+        // Occurrences are taken per class; a mention of another class is not a repeat.
+        let extractions = [
+            mention(1),
+            data::Extraction::new("medication".to_string(), "aspirin".to_string(), 2, 0, None),
+        ];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, false);
+        assert_eq!(aligned[1].char_interval.as_ref().unwrap().start_pos, 25);
+        assert!(!aligned[1].duplicate);
+    }
+
+    #[test]
+    fn test_alignment_edit_distance() {
+        let resolver = Resolver::new(false, None, None, false);
+        let source = "The parcel was recieved on Monday after a long treat-\n  ment of the claim.";
+        let extractions = [
+            data::Extraction::new("event".to_string(), "received".to_string(), 1, 0, None),
+            data::Extraction::new("process".to_string(), "treatment".to_string(), 2, 0, None),
+            data::Extraction::new("day".to_string(), "Tuesday".to_string(), 3, 0, None),
+        ];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), true, 0.75, false);
+
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchFuzzy));
+        assert_eq!(aligned[0].edit_distance, Some(2));
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "recieved"
+        );
+        assert_eq!(aligned[1].edit_distance, Some(0));
+        assert_eq!(
+            char_slice(source, aligned[1].char_interval.as_ref().unwrap()),
+            "treat-\n  ment"
+        );
+        assert!(aligned[2].alignment_status.is_none());
+
+        let aligned = resolver.align(&extractions[..1], source, 0, Some(0), false, 0.75, false);
+        assert!(aligned[0].alignment_status.is_none());
+    }
+
+    #[test]
+    fn test_alignment_with_token_normalizer() {
+        use crate::normalization::{CaseFold, ChineseVariantFolding, FullWidthToHalfWidth, Nfkc};
+
+        let source = "襲人笑道：你們怎麼連這個也忘了。";
+        let extractions = [data::Extraction::new(
+            "character".to_string(),
+            "袭人笑".to_string(),
+            1,
+            0,
+            None,
+        )];
+        let resolver = Resolver::new(false, None, None, false);
+        let aligned = resolver.align(&extractions, source, 0, Some(0), true, 1.0, false);
+        assert!(aligned[0].alignment_status.is_none());
+
+        // The normalizer applies to exact matching too, without fuzzy alignment.
+        let resolver = resolver.with_token_normalizer(ChineseVariantFolding);
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 1.0, false);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchExact));
+        assert_eq!(char_slice(source, aligned[0].char_interval.as_ref().unwrap()), "襲人笑");
+
+        // The default stems English words, so inflections still line up.
+        let source = "Fever was treated before the patient's connections were reviewed.";
+        let extractions = [data::Extraction::new(
+            "event".to_string(),
+            "Treating fevers".to_string(),
+            1,
+            0,
+            None,
+        )];
+        let resolver = Resolver::new(false, None, None, false);
+        let aligned = resolver.align(&extractions, source, 0, Some(0), true, 0.6, false);
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "Fever was treated"
+        );
+
+        let source = "Order ABC shipped on Monday.";
+        let extractions = [data::Extraction::new(
+            "order".to_string(),
+            "Ｏｒｄｅｒ ＡＢＣ".to_string(),
+            1,
+            0,
+            None,
+        )];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 1.0, false);
+        assert!(aligned[0].alignment_status.is_none());
+
+        let resolver = resolver.with_token_normalizer(NormalizerChain::new().with(FullWidthToHalfWidth).with(CaseFold));
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 1.0, false);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchExact));
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "Order ABC"
+        );
+
+        // Truncated and partial matches compare normalized tokens as well.
+        let extractions = [data::Extraction::new(
+            "order".to_string(),
+            "ＯＲＤＥＲ ＡＢＣ ｄｅｌｉｖｅｒｅｄ".to_string(),
+            1,
+            0,
+            None,
+        )];
+        let resolver = resolver.with_token_normalizer(NormalizerChain::new().with(Nfkc).with(CaseFold));
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 1.0, true);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchLesser));
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "Order ABC"
+        );
+    }
+
+    #[test]
+    fn test_alignment_sentence_fallback() {
+        let source =
+            "The patient was admitted on Monday. Her blood pressure was high on admission. She left on Friday.";
+        let extractions = [
+            data::Extraction::new(
+                "finding".to_string(),
+                "elevated BP".to_string(),
+                1,
+                0,
+                Some(serde_json::json!({"measurement": "blood pressure"})),
+            ),
+            data::Extraction::new("finding".to_string(), "tachycardia".to_string(), 2, 0, None),
+        ];
+        let resolver = Resolver::new(false, None, None, false);
+        let aligned = resolver.align(&extractions, source, 0, Some(0), true, 0.75, false);
+        assert!(aligned[0].char_interval.is_none());
+
+        let resolver = resolver.with_sentence_fallback(0.2);
+        let aligned = resolver.align(&extractions, source, 0, Some(0), true, 0.75, false);
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchSentence));
+        assert_eq!(
+            char_slice(source, aligned[0].char_interval.as_ref().unwrap()),
+            "Her blood pressure was high on admission."
+        );
+        // Nothing in the chunk resembles it, so it stays unaligned.
+        assert!(aligned[1].alignment_status.is_none());
+        assert!(aligned[1].char_interval.is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match_window() {
+        let aligner = WordAligner::new();
+        let tokens = |text: &str| -> Vec<String> { text.split_whitespace().map(str::to_string).collect() };
+
+        let source = tokens("the race involved many runners and running race participants");
+        let found = aligner.find_fuzzy_match(&tokens("running races"), &source, 0.75);
+        assert_eq!(found, Some((6, 2)));
+
+        // Extraction tokens scattered far apart do not make a match.
+        let source = tokens("alpha b c d e f g h i j k l m n o p q r s t beta");
+        assert_eq!(aligner.find_fuzzy_match(&tokens("alpha beta"), &source, 0.75), None);
+        let found = aligner.find_fuzzy_match(&tokens("alpha x beta"), &tokens("alpha y beta"), 0.6);
+        assert_eq!(found, Some((0, 3)));
     }
 
     #[test]
-    fn test_tokenizer() {
-        let tokenized = tokenizer::tokenize("Hello world! 测试");
-        assert_eq!(tokenized.tokens.len(), 3);
-        assert_eq!(tokenized.tokens[0].text, "Hello");
-        assert_eq!(tokenized.tokens[1].text, "world!");
-        assert_eq!(tokenized.tokens[2].text, "测试");
+    fn test_fuzzy_alignment_long_chunk() {
+        let resolver = Resolver::new(false, None, None, false);
+        let filler = "word ".repeat(500);
+        let source = format!("{}the quick brown foxes jumped {}", filler, filler);
+        let ex = data::Extraction::new("event".to_string(), "quick brown fox jumped".to_string(), 1, 0, None);
+        let aligned = resolver.align(&[ex], &source, 0, Some(0), true, 0.75, false);
+
+        assert_eq!(aligned[0].alignment_status, Some(data::AlignmentStatus::MatchFuzzy));
+        let interval = aligned[0].char_interval.as_ref().unwrap();
+        assert_eq!(char_slice(&source, interval), "quick brown foxes jumped");
+    }
+
+    fn char_slice(text: &str, interval: &data::CharInterval) -> String {
+        text.chars()
+            .skip(interval.start_pos)
+            .take(interval.end_pos - interval.start_pos)
+            .collect()
+    }
+
+    #[test]
+    fn test_alignment_chinese() {
+        let resolver = Resolver::new(false, None, None, true);
+        let source = "宝玉今日穿了一件月白缎子袍子，腰系丝绦，头戴紫金冠，脚蹬云头履，\n正在怡红院中读书。忽见袭人端了一盏碧螺春茶进来，笑道：\"二爷，\n林姑娘在潇湘馆等您呢。\"";
+        let extractions = [
+            data::Extraction::new("characters".to_string(), "袭人".to_string(), 1, 0, None),
+            data::Extraction::new("locations".to_string(), "怡红院".to_string(), 2, 0, None),
+            data::Extraction::new("objects".to_string(), "碧螺春茶".to_string(), 3, 0, None),
+            data::Extraction::new("clothing".to_string(), "紫金冠".to_string(), 4, 0, None),
+        ];
+        let aligned = resolver.align(&extractions, source, 0, Some(0), false, 0.75, false);
+
+        for extraction in &aligned {
+            assert_eq!(extraction.alignment_status, Some(data::AlignmentStatus::MatchExact));
+            let interval = extraction.char_interval.as_ref().unwrap();
+            assert_eq!(char_slice(source, interval), extraction.extraction_text);
+        }
+        let xiren = aligned[0].char_interval.as_ref().unwrap();
+        assert_eq!((xiren.start_pos, xiren.end_pos), (44, 46));
+        let tokens = aligned[0].token_interval.as_ref().unwrap();
+        assert_eq!(tokens.end_index - tokens.start_index, 2);
+    }
+
+    #[test]
+    fn test_alignment_mixed_script_and_punctuation() {
+        let resolver = Resolver::new(false, None, None, false);
+        let source = "Hello世界! 林黛玉 met Bob Stone.";
+        let extractions = [
+            data::Extraction::new("greeting".to_string(), "世界".to_string(), 1, 0, None),
+            data::Extraction::new("person".to_string(), "Bob Stone".to_string(), 2, 0, None),
+            data::Extraction::new("person".to_string(), "林黛玉".to_string(), 3, 0, None),
+        ];
+        let aligned = resolver.align(&extractions, source, 5, Some(100), false, 0.75, false);
+
+        let intervals: Vec<_> = aligned
+            .iter()
+            .map(|e| {
+                e.char_interval
+                    .as_ref()
+                    .map(|ci| (ci.start_pos - 100, ci.end_pos - 100))
+            })
+            .collect();
+        assert_eq!(intervals, vec![Some((5, 7)), Some((17, 26)), Some((9, 12))]);
+        let bob = aligned[1].token_interval.as_ref().unwrap();
+        assert_eq!((bob.start_index, bob.end_index), (5 + 8, 5 + 10));
     }
 
     #[test]
@@ -898,6 +1676,40 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_ignores_reasoning() {
+        let resolver = Resolver::new(true, Some("_index".to_string()), Some("_attributes".to_string()), true);
+        let output = "<think>\nAn example:\n```yaml\nextractions:\n  - person: Eve\n```\n</think>\n```yaml\nextractions:\n  - person: Bob\n```";
+        let res = resolver.resolve(output, false).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].extraction_text, "Bob");
+    }
+
+    #[test]
+    fn test_lenient_parsing() {
+        let output = "Sure, here you go:\n```json\n{\"extractions\": [\n  {\"person\": 'Alice', \"person_index\": 1},\n  {\"person\": \"Bob\", \"person_index\": 2},\n]}\n```";
+        let strict = Resolver::default();
+        assert!(strict.resolve(output, false).is_err());
+
+        let (extractions, repairs) = strict
+            .clone()
+            .with_lenient_parsing(true)
+            .resolve_with_repairs(output, false)
+            .unwrap();
+        assert_eq!(extractions.len(), 2);
+        assert_eq!(extractions[1].extraction_text, "Bob");
+        assert_eq!(
+            repairs,
+            vec![ParseRepair::ConvertedSingleQuotes, ParseRepair::RemovedTrailingCommas]
+        );
+
+        let truncated = "```yaml\nextractions:\n  - person: Alice\n  - person: Bo";
+        let resolver = Resolver::new(true, None, None, true).with_lenient_parsing(true);
+        let (extractions, repairs) = resolver.resolve_with_repairs(truncated, false).unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert!(repairs.iter().any(ParseRepair::is_truncation));
+    }
+
     #[test]
     fn test_parse_simple_yaml_array() {
         let resolver = Resolver::new(true, None, None, true);
@@ -961,7 +1773,7 @@ objects:
   - 云头履
 ```"#;
         let result = resolver.parse_extractions_from_string(yaml).unwrap();
-        assert!(result.len() > 0);
+        assert!(!result.is_empty());
 
         // Check that we got the expected names (in any order)
         let texts: Vec<&str> = result.iter().map(|e| e.extraction_text.as_str()).collect();
@@ -112,9 +112,7 @@ pub fn tokenize(text: &str) -> TokenizedText {
             token.token_type = TokenType::Number;
         } else if SLASH_ABBREV_REGEX.is_match(matched_text) {
             token.token_type = TokenType::Acronym;
-        } else if CHINESE_REGEX.is_match(matched_text) {
-            token.token_type = TokenType::Word;
-        } else if WORD_REGEX.is_match(matched_text) {
+        } else if CHINESE_REGEX.is_match(matched_text) || WORD_REGEX.is_match(matched_text) {
             token.token_type = TokenType::Word;
         } else {
             token.token_type = TokenType::Punctuation;
@@ -315,7 +313,8 @@ mod tests {
         for token in &tokenized.tokens {
             let token_text =
                 extract_text_from_char_indices(text, token.char_interval.start_pos, token.char_interval.end_pos);
-            if token.token_type == TokenType::Word && token_text.chars().any(|c| c >= '\u{4e00}' && c <= '\u{9fff}') {
+            if token.token_type == TokenType::Word && token_text.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c))
+            {
                 found_chinese_word = true;
                 break;
             }
@@ -346,7 +345,7 @@ mod tests {
 
         let has_chinese = tokenized.tokens.iter().any(|t| {
             let token_text = extract_text_from_char_indices(text, t.char_interval.start_pos, t.char_interval.end_pos);
-            t.token_type == TokenType::Word && token_text.chars().any(|c| c >= '\u{4e00}' && c <= '\u{9fff}')
+            t.token_type == TokenType::Word && token_text.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c))
         });
 
         assert!(has_english, "Should find English words");
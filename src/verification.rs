@@ -0,0 +1,432 @@
+/*!
+Second-stage verification of aligned extractions.
+
+A `Verifier` re-asks a language model, in batches, whether each aligned
+extraction is supported by the sentence it was found in and whether its
+attributes are right. The verdict becomes the extraction's `confidence` and
+`rationale`; with a threshold, extractions below it are dropped. This is worth
+its extra requests where a wrong extraction is costly, as in medical text.
+`Annotator::with_verifier` runs a verifier on every document as part of
+annotation.
+
+Usage example:
+    let verifier = Verifier::new(language_model).with_batch_size(20).with_threshold(0.5);
+    verifier.verify_document(&mut annotated_document).await?;
+*/
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::chunking::{SentenceIterator, get_char_interval};
+use crate::data::{AnnotatedDocument, AttributeValue, CharInterval, Extraction};
+use crate::inference::{BaseLanguageModel, InferenceOutputError, split_reasoning};
+use crate::lenient;
+use crate::tokenizer::tokenize;
+
+/// The verifier's judgement of one extraction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub supported: bool,
+    /// Whether the attributes are right; `None` when the model did not say.
+    pub attributes_correct: Option<bool>,
+    pub confidence: f64,
+    pub rationale: Option<String>,
+}
+
+impl Verdict {
+    /// Reads a verdict from one object of the model's JSON answer.
+    ///
+    /// Without an explicit `confidence`, a supported extraction gets 1.0 and
+    /// an unsupported one 0.0. Wrong attributes halve the confidence.
+    fn from_json(value: &Value) -> Option<Self> {
+        let supported = value.get("supported")?.as_bool()?;
+        let attributes_correct = value.get("attributes_correct").and_then(Value::as_bool);
+        let mut confidence = value
+            .get("confidence")
+            .and_then(Value::as_f64)
+            .unwrap_or(if supported { 1.0 } else { 0.0 })
+            .clamp(0.0, 1.0);
+        if attributes_correct == Some(false) {
+            confidence /= 2.0;
+        }
+        let rationale = value
+            .get("rationale")
+            .and_then(Value::as_str)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        Some(Self {
+            supported,
+            attributes_correct,
+            confidence,
+            rationale,
+        })
+    }
+}
+
+/// Checks the extractions of an annotated document after resolution, as run
+/// by [`crate::annotation::Annotator::with_verifier`].
+#[async_trait]
+pub trait ExtractionVerifier: Send + Sync {
+    async fn verify_document(&self, document: &mut AnnotatedDocument) -> Result<(), InferenceOutputError>;
+}
+
+/// Verifies extractions against their source sentences with a language model.
+pub struct Verifier<L: BaseLanguageModel> {
+    language_model: L,
+    batch_size: usize,
+    threshold: Option<f64>,
+}
+
+impl<L: BaseLanguageModel> Verifier<L> {
+    pub fn new(language_model: L) -> Self {
+        Self {
+            language_model,
+            batch_size: 10,
+            threshold: None,
+        }
+    }
+
+    /// Sets how many extractions are verified per prompt (default 10).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Drops verified extractions whose confidence is below `threshold`.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Verifies every document, see [`Verifier::verify_document`].
+    pub async fn verify(&self, documents: &mut [AnnotatedDocument]) -> Result<(), InferenceOutputError> {
+        for document in documents {
+            self.verify_document(document).await?;
+        }
+        Ok(())
+    }
+
+    /// Sets `confidence` and `rationale` on every aligned extraction of
+    /// `document`, replacing any earlier confidence, and drops those below the
+    /// threshold. Unaligned extractions, and extractions the model gave no
+    /// verdict for, are left unchanged.
+    pub async fn verify_document(&self, document: &mut AnnotatedDocument) -> Result<(), InferenceOutputError> {
+        let (Some(text), Some(extractions)) = (document.text.as_deref(), document.extractions.as_mut()) else {
+            return Ok(());
+        };
+        let sentences = sentence_intervals(text);
+        let chars: Vec<char> = text.chars().collect();
+        let candidates: Vec<(usize, String)> = extractions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, extraction)| {
+                let (start, end) = position(extraction)?;
+                Some((index, source_sentence(&chars, &sentences, start, end)))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+
+        let batches: Vec<&[(usize, String)]> = candidates.chunks(self.batch_size).collect();
+        let prompts: Vec<String> = batches
+            .iter()
+            .map(|batch| {
+                let items: Vec<(&Extraction, &str)> = batch
+                    .iter()
+                    .map(|(index, sentence)| (&extractions[*index], sentence.as_str()))
+                    .collect();
+                verification_prompt(&items)
+            })
+            .collect();
+        let outputs = self.language_model.infer(&prompts, None).await?;
+
+        // Every batch is parsed before any extraction is touched, so a failed
+        // batch leaves the document as it was.
+        let mut batch_verdicts = Vec::with_capacity(batches.len());
+        for (batch, scored_outputs) in batches.iter().zip(outputs) {
+            let answer = scored_outputs
+                .first()
+                .and_then(|scored| scored.output.as_deref())
+                .ok_or_else(|| InferenceOutputError::new("No scored outputs from verifier."))?;
+            batch_verdicts.push(parse_verdicts(answer, batch.len())?);
+        }
+        if batch_verdicts.len() < batches.len() {
+            return Err(InferenceOutputError::new("Missing verifier outputs for some batches."));
+        }
+
+        let mut verified = HashSet::new();
+        for (batch, verdicts) in batches.iter().zip(batch_verdicts) {
+            for ((index, _), verdict) in batch.iter().zip(verdicts) {
+                if let Some(verdict) = verdict {
+                    extractions[*index].confidence = Some(verdict.confidence);
+                    extractions[*index].rationale = verdict.rationale;
+                    verified.insert(*index);
+                }
+            }
+        }
+
+        if let Some(threshold) = self.threshold {
+            let mut index = 0;
+            extractions.retain(|extraction| {
+                let keep = !verified.contains(&index) || extraction.confidence.is_some_and(|c| c >= threshold);
+                index += 1;
+                keep
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<L: BaseLanguageModel> ExtractionVerifier for Verifier<L> {
+    async fn verify_document(&self, document: &mut AnnotatedDocument) -> Result<(), InferenceOutputError> {
+        Verifier::verify_document(self, document).await
+    }
+}
+
+fn position(extraction: &Extraction) -> Option<(usize, usize)> {
+    let interval = extraction.char_interval.as_ref()?;
+    Some((interval.start_pos?, interval.end_pos?))
+}
+
+/// Character intervals of the sentences of `text`.
+fn sentence_intervals(text: &str) -> Vec<CharInterval> {
+    let tokenized_text = tokenize(text);
+    let Ok(sentences) = SentenceIterator::new(&tokenized_text, 0) else {
+        return Vec::new();
+    };
+    sentences
+        .filter_map(|sentence| get_char_interval(&tokenized_text, &sentence).ok())
+        .collect()
+}
+
+/// The sentences covering `[start, end)`, or the span itself when no sentence
+/// does.
+fn source_sentence(chars: &[char], sentences: &[CharInterval], start: usize, end: usize) -> String {
+    let covering: Vec<(usize, usize)> = sentences
+        .iter()
+        .filter_map(|s| Some((s.start_pos?, s.end_pos?)))
+        .filter(|(s, e)| *s < end.max(start + 1) && start < *e)
+        .collect();
+    let from = covering.first().map_or(start, |(s, _)| *s).min(start);
+    let to = covering.last().map_or(end, |(_, e)| *e).max(end).min(chars.len());
+    chars[from.min(to)..to].iter().collect()
+}
+
+fn attributes_text(extraction: &Extraction) -> Option<String> {
+    let attributes = extraction.attributes.as_ref().filter(|a| !a.is_empty())?;
+    let mut keys: Vec<&String> = attributes.keys().collect();
+    keys.sort();
+    let rendered: Vec<String> = keys
+        .into_iter()
+        .map(|key| match &attributes[key] {
+            AttributeValue::Single(value) => format!("{}={}", key, value),
+            AttributeValue::Multiple(values) => format!("{}=[{}]", key, values.join(", ")),
+        })
+        .collect();
+    Some(rendered.join("; "))
+}
+
+fn verification_prompt(items: &[(&Extraction, &str)]) -> String {
+    let mut prompt = String::from(
+        "For each numbered extraction below, decide whether its text is supported by the source sentence \
+         and whether its attributes are correct.\n\n",
+    );
+    for (number, (extraction, sentence)) in (1..).zip(items) {
+        let _ = writeln!(prompt, "{}. class: {}", number, extraction.extraction_class);
+        let _ = writeln!(prompt, "   text: {}", extraction.extraction_text);
+        if let Some(attributes) = attributes_text(extraction) {
+            let _ = writeln!(prompt, "   attributes: {}", attributes);
+        }
+        let _ = writeln!(prompt, "   sentence: {}", sentence.trim());
+    }
+    prompt.push_str(
+        "\nAnswer with a JSON array holding one object per extraction, for example:\n\
+         [{\"id\": 1, \"supported\": true, \"attributes_correct\": true, \"confidence\": 0.9, \
+         \"rationale\": \"The sentence states it.\"}]\n",
+    );
+    prompt
+}
+
+/// Parses the model's answer into one optional verdict per item, matched by
+/// `id` when present and by position otherwise.
+///
+/// Reasoning blocks are dropped and the array is read with the same lenient
+/// parsing as extraction output.
+fn parse_verdicts(answer: &str, len: usize) -> Result<Vec<Option<Verdict>>, InferenceOutputError> {
+    let (_, answer) = split_reasoning(answer);
+    let (content, _) = lenient::select_content(&answer, true, false);
+    let (value, _) = lenient::parse_json(&content)
+        .map_err(|e| InferenceOutputError::new(format!("Failed to parse verifier answer: {}", e)))?;
+    let Value::Array(values) = value else {
+        return Err(InferenceOutputError::new(format!(
+            "Verifier answer is not a JSON array: {}",
+            answer
+        )));
+    };
+
+    let mut verdicts = vec![None; len];
+    for (position, value) in values.iter().enumerate() {
+        let slot = match value.get("id").and_then(Value::as_u64) {
+            Some(id) => (id as usize).checked_sub(1),
+            None => Some(position),
+        };
+        if let Some(slot) = slot.filter(|slot| *slot < len) {
+            verdicts[slot] = Verdict::from_json(value);
+        }
+    }
+    Ok(verdicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::ScoredOutput;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Answers the n-th prompt of a call with the n-th answer, cycling.
+    struct MockVerifierModel {
+        answers: Vec<String>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for MockVerifierModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: Option<HashMap<String, Value>>,
+        ) -> Result<Vec<Vec<ScoredOutput>>, InferenceOutputError> {
+            self.prompts.lock().unwrap().extend(batch_prompts.iter().cloned());
+            Ok((0..batch_prompts.len())
+                .map(|i| {
+                    let answer = self.answers[i % self.answers.len()].clone();
+                    vec![ScoredOutput::new(Some(1.0), Some(answer))]
+                })
+                .collect())
+        }
+    }
+
+    fn model(answer: &str) -> MockVerifierModel {
+        models(&[answer])
+    }
+
+    fn models(answers: &[&str]) -> MockVerifierModel {
+        MockVerifierModel {
+            answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            prompts: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn document() -> AnnotatedDocument {
+        let extraction = |class: &str, text: &str, span: Option<(usize, usize)>| {
+            Extraction::new(
+                class.to_string(),
+                text.to_string(),
+                None,
+                span.map(|(start, end)| CharInterval::new(Some(start), Some(end))),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+        };
+        AnnotatedDocument::new(
+            Some("doc_1".to_string()),
+            Some(vec![
+                extraction("medication", "aspirin", Some((13, 20))),
+                extraction("medication", "ibuprofen", Some((34, 43))),
+                extraction("medication", "heparin", None),
+            ]),
+            Some("Patient took aspirin. No one took ibuprofen.".to_string()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_verifier_sets_confidence_and_rationale() {
+        let answer = r#"```json
+[{"id": 2, "supported": false, "rationale": "Negated."},
+ {"id": 1, "supported": true, "attributes_correct": false, "confidence": 0.8}]
+```"#;
+        let verifier = Verifier::new(model(answer));
+        let mut document = document();
+        verifier.verify_document(&mut document).await.unwrap();
+
+        let extractions = document.extractions.as_ref().unwrap();
+        assert_eq!(extractions[0].confidence, Some(0.4));
+        assert_eq!(extractions[1].confidence, Some(0.0));
+        assert_eq!(extractions[1].rationale.as_deref(), Some("Negated."));
+        assert_eq!(extractions[2].confidence, None);
+
+        let prompts = verifier.language_model.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("sentence: Patient took aspirin."));
+        assert!(!prompts[0].contains("heparin"));
+    }
+
+    #[tokio::test]
+    async fn test_verifier_threshold_drops_unsupported() {
+        let answer = r#"[{"supported": true, "confidence": 0.9}]"#;
+        let verifier = Verifier::new(model(answer)).with_batch_size(1).with_threshold(0.5);
+        let mut document = document();
+        verifier.verify_document(&mut document).await.unwrap();
+
+        assert_eq!(verifier.language_model.prompts.lock().unwrap().len(), 2);
+        let texts: Vec<_> = document
+            .extractions
+            .unwrap()
+            .iter()
+            .map(|e| e.extraction_text.clone())
+            .collect();
+        assert_eq!(texts, vec!["aspirin", "ibuprofen", "heparin"]);
+
+        let verifier = Verifier::new(model(r#"[{"supported": true}, {"supported": false}]"#)).with_threshold(0.5);
+        let mut document = self::document();
+        verifier.verify_document(&mut document).await.unwrap();
+        let texts: Vec<_> = document
+            .extractions
+            .unwrap()
+            .iter()
+            .map(|e| e.extraction_text.clone())
+            .collect();
+        assert_eq!(texts, vec!["aspirin", "heparin"]);
+    }
+
+    #[tokio::test]
+    async fn test_verifier_rejects_malformed_answer() {
+        let verifier = Verifier::new(model("I cannot tell."));
+        assert!(verifier.verify_document(&mut document()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verifier_leaves_document_unchanged_when_a_batch_fails() {
+        let verifier = Verifier::new(models(&[r#"[{"supported": false}]"#, "I cannot tell."]))
+            .with_batch_size(1)
+            .with_threshold(0.5);
+        let mut document = document();
+        assert!(verifier.verify_document(&mut document).await.is_err());
+
+        let extractions = document.extractions.unwrap();
+        assert_eq!(extractions.len(), 3);
+        assert!(extractions.iter().all(|e| e.confidence.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_verifier_skips_reasoning_and_prose() {
+        let answer = "<think>Check [1] against its sentence.</think>\nHere is the verdict:\n\
+                      [{\"id\": 1, \"supported\": true, \"confidence\": 0.7,}]";
+        let verifier = Verifier::new(model(answer));
+        let mut document = document();
+        verifier.verify_document(&mut document).await.unwrap();
+
+        let extractions = document.extractions.unwrap();
+        assert_eq!(extractions[0].confidence, Some(0.7));
+        assert_eq!(extractions[1].confidence, None);
+    }
+}
@@ -711,7 +711,7 @@ fn build_chinese_classical_html(
     for extraction in extractions {
         category_counts
             .entry(extraction.extraction_class.clone())
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(extraction);
     }
 
@@ -1450,8 +1450,8 @@ mod tests {
     fn test_options_default() {
         let options = VisualizeOptions::default();
         assert_eq!(options.animation_speed, 1.0);
-        assert_eq!(options.show_legend, true);
-        assert_eq!(options.gif_optimized, true);
+        assert!(options.show_legend);
+        assert!(options.gif_optimized);
         assert_eq!(options.context_chars, 150);
     }
 